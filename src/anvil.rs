@@ -6,6 +6,7 @@ use crate::Controls;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::Canvas;
+use sdl2::render::Texture;
 use sdl2::render::TextureCreator;
 use sdl2::video::Window;
 
@@ -17,33 +18,57 @@ use std::path::Path;
 use std::time::Duration;
 
 pub struct Bangs<'a> {
-    image: sdl2::render::Texture<'a>,
-    rect: Rect,
+    pub image: sdl2::render::Texture<'a>,
+    pub rect: Rect,
 }
 
 pub struct BangSpawner {
     countdown: i32,
 }
 
-fn update_note(n: &mut Bangs) -> bool {
-    n.rect.y = n.rect.y + 5;
+pub fn update_note(n: &mut Bangs, step: i32) -> bool {
+    n.rect.y += step;
 
-    if n.rect.y >= 480 {
+    if n.rect.y >= SCREEN_HEIGHT as i32 {
         return true;
     }
 
     false
 }
 
-fn find_in_vec(v: &Vec<Bangs>, r: Rect) -> i64 {
+// Better tools make off-beat hits cost less
+fn hit_points(dist: i32, tools: i32) -> i32 {
+    100 - dist * 4 / (4 + tools)
+}
+
+pub fn find_in_vec(v: &Vec<Bangs>, r: Rect) -> i64 {
     for (i, note) in v.iter().enumerate() {
-        if let Some(_) = note.rect.intersection(r) {
+        if note.rect.intersection(r).is_some() {
             return i as i64;
         }
     }
     -1
 }
 
+// Colored note for a lane
+pub fn lane_texture<T>(texture_creator: &TextureCreator<T>, lane: usize) -> Texture<'_> {
+    match lane {
+        0 => texture_creator
+            .load_texture(Path::new("assets/BangViolet.png"))
+            .unwrap(),
+        1 => texture_creator
+            .load_texture(Path::new("assets/BangRed.png"))
+            .unwrap(),
+        2 => texture_creator
+            .load_texture(Path::new("assets/BangBlue.png"))
+            .unwrap(),
+        3 => texture_creator
+            .load_texture(Path::new("assets/BangYellow.png"))
+            .unwrap(),
+        _ => panic!("This should never happen, generated num outside range"),
+    }
+}
+
 fn spawn<'a, T>(
     bangs: &mut Vec<Bangs<'a>>,
     spawner: &mut BangSpawner,
@@ -56,7 +81,7 @@ fn spawn<'a, T>(
 
     let mut rng = rand::thread_rng();
 
-    let recs = vec![
+    let recs = [
         Rect::new(24, -60, 120, 60),
         Rect::new(168, -60, 120, 60),
         Rect::new(312, -60, 120, 60),
@@ -65,21 +90,7 @@ fn spawn<'a, T>(
 
     let i: usize = rng.gen_range(0..4);
 
-    let t = match i {
-        0 => texture_creator
-            .load_texture(Path::new("assets/BangViolet.png"))
-            .unwrap(),
-        1 => texture_creator
-            .load_texture(Path::new("assets/BangRed.png"))
-            .unwrap(),
-        2 => texture_creator
-            .load_texture(Path::new("assets/BangBlue.png"))
-            .unwrap(),
-        3 => texture_creator
-            .load_texture(Path::new("assets/BangYellow.png"))
-            .unwrap(),
-        _ => panic!("This should never happen, generated num outside range"),
-    };
+    let t = lane_texture(texture_creator, i);
 
     bangs.push(Bangs {
         image: t,
@@ -110,13 +121,14 @@ fn exit_anvil<T>(
     let w2 = line2.query().width;
     let h2 = line2.query().height;
 
-    let x1 = (600 - w1) / 2;
-    let x2 = (600 - w2) / 2;
-    let y1 = (480 - h1 - h2) / 3;
-    let y2 = 2 * ((480 - h1 - h2) / 3);
+    let (sw, sh) = (SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
+    let x1 = (sw - w1 as i32) / 2;
+    let x2 = (sw - w2 as i32) / 2;
+    let y1 = (sh - h1 as i32 - h2 as i32) / 3;
+    let y2 = 2 * y1;
 
-    let r1 = Rect::new(x1 as i32, y1 as i32, w1, h1);
-    let r2 = Rect::new(x2 as i32, y2 as i32, w2, h2);
+    let r1 = Rect::new(x1, y1, w1, h1);
+    let r2 = Rect::new(x2, y2, w2, h2);
 
     canvas.set_draw_color(Color::RGB(0, 0, 0));
     canvas.clear();
@@ -142,6 +154,29 @@ pub fn run_anvil(game: &mut Game) -> bool {
         None => return false,
     };
 
+    // Bars can always be started, anything else has to be due for hammering
+    let shaped = match game.state.inventory[index].form {
+        Form::Bar => None,
+        Form::Crucible | Form::Scrap => {
+            display_error(game, "Smelt it into a bar first");
+            return false;
+        }
+        f => Some(f),
+    };
+    if shaped.is_some() {
+        match game.state.inventory[index].next_step() {
+            Some(StepKind::Hammer) => (),
+            Some(next) => {
+                display_error(game, &format!("Next step: {}", next));
+                return false;
+            }
+            None => {
+                display_error(game, "Item is already finished");
+                return false;
+            }
+        }
+    }
+
     // Multiplier for additional value
     // TODO: Let player know that white can't be used if selected
     let mult = match game.state.inventory[index].temp_val() {
@@ -153,8 +188,15 @@ pub fn run_anvil(game: &mut Game) -> bool {
         Temp::Over => 1f32,
     };
 
-    // Pick form
-    let form = pick_form(game);
+    // A better anvil adds 10% per tier
+    let mult = mult * (1.0 + 0.1 * game.state.upgrades.anvil_tier as f32);
+    let tools = game.state.upgrades.tools_tier;
+
+    // Pick form, unless it's already being shaped into one
+    let form = match shaped {
+        Some(x) => Some(x),
+        None => pick_form(game),
+    };
 
     // Handle stuff
     let form = match form {
@@ -162,6 +204,12 @@ pub fn run_anvil(game: &mut Game) -> bool {
         Some(x) => x,
     };
 
+    // Starting a new piece takes its weight of metal off the bar
+    if shaped.is_none() && game.state.inventory[index].weight < form.weight() {
+        display_error(game, &format!("Needs {} units of metal", form.weight()));
+        return false;
+    }
+
     // Load Bangs
     let bang = texture_creator
         .load_texture(Path::new("assets/Bang.png"))
@@ -179,6 +227,7 @@ pub fn run_anvil(game: &mut Game) -> bool {
 
     // Notes
     let mut notes = Vec::<Bangs>::new();
+    let step = game.settings.note_step();
 
     // Points
     let mut points = 0;
@@ -190,62 +239,58 @@ pub fn run_anvil(game: &mut Game) -> bool {
         game.canvas.clear();
 
         // Handle events
-        if !handle_events(&mut cs, &mut game.event_pump) {
+        if !handle_events(&mut cs, &mut game.input) {
             break;
         }
 
         // Update notes
-        for mut note in notes.iter_mut() {
-            if update_note(&mut note) {
+        for note in notes.iter_mut() {
+            if update_note(note, step) {
                 break 'running;
             }
         }
 
         // Handle logic
-        if cs.left {
+        if cs.lanes[0] {
             let i = find_in_vec(&notes, r1);
             if i >= 0 {
-                let val = notes[i as usize].rect.y - r1.y;
-                let val = if val < 0 { val * -1 } else { val };
-                points += 100 - val;
+                let val = (notes[i as usize].rect.y - r1.y).abs();
+                points += hit_points(val, tools);
                 notes.remove(i as usize);
-                cs.left = false;
+                cs.lanes[0] = false;
             } else {
                 break;
             }
         }
-        if cs.up {
+        if cs.lanes[1] {
             let i = find_in_vec(&notes, r2);
             if i >= 0 {
-                let val = notes[i as usize].rect.y - r2.y;
-                let val = if val < 0 { val * -1 } else { val };
-                points += 100 - val;
+                let val = (notes[i as usize].rect.y - r2.y).abs();
+                points += hit_points(val, tools);
                 notes.remove(i as usize);
-                cs.up = false;
+                cs.lanes[1] = false;
             } else {
                 break;
             }
         }
-        if cs.down {
+        if cs.lanes[2] {
             let i = find_in_vec(&notes, r3);
             if i >= 0 {
-                let val = notes[i as usize].rect.y - r3.y;
-                let val = if val < 0 { val * -1 } else { val };
-                points += 100 - val;
+                let val = (notes[i as usize].rect.y - r3.y).abs();
+                points += hit_points(val, tools);
                 notes.remove(i as usize);
-                cs.down = false;
+                cs.lanes[2] = false;
             } else {
                 break;
             }
         }
-        if cs.right {
+        if cs.lanes[3] {
             let i = find_in_vec(&notes, r4);
             if i >= 0 {
-                let val = notes[i as usize].rect.y - r4.y;
-                let val = if val < 0 { val * -1 } else { val };
-                points += 100 - val;
+                let val = (notes[i as usize].rect.y - r4.y).abs();
+                points += hit_points(val, tools);
                 notes.remove(i as usize);
-                cs.right = false;
+                cs.lanes[3] = false;
             } else {
                 break;
             }
@@ -267,16 +312,32 @@ pub fn run_anvil(game: &mut Game) -> bool {
         game.canvas.copy(&bang, None, r4).unwrap();
 
         // Update game.canvas
-        game.canvas.present();
+        game.toasts.present(&mut game.canvas);
 
         // Sleep
         ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
 
-    // Update value + form + location (incorrect for rn)
-    game.state.inventory[index].value = (mult * (points as f32)) as i32;
-    game.state.inventory[index].form = form;
-    game.state.inventory[index].location = Location::Storage;
+    // Whatever isn't used stays behind as a bar
+    let left = game.state.inventory[index].weight - form.weight();
+    if shaped.is_none() && left > 0 {
+        let mut rest = game.state.inventory[index].clone();
+        rest.weight = left;
+        rest.location = Location::Storage;
+        rest.appraise();
+        game.state.inventory.push(rest);
+        game.state.inventory[index].weight = form.weight();
+    }
+
+    // Record the session, the item loses heat while being worked
+    let score = (mult * (points as f32)) as i32;
+    game.state.record_score(score);
+    let item = &mut game.state.inventory[index];
+    item.history.push(Step::Hammer(score));
+    item.form = form;
+    item.location = Location::Storage;
+    item.temp = (item.temp - 1000).max(70);
+    item.appraise();
 
     // Create font to pass to exit_anvil
     let mut font = game
@@ -0,0 +1,138 @@
+use super::game::*;
+
+use std::fmt;
+
+// Times of day are in minutes after midnight
+// The smith gets up at six and the day is over at ten at night
+pub const DAY_START: i32 = 6 * 60;
+pub const DAY_END: i32 = 22 * 60;
+
+// Hours customers come by the shop
+pub const SHOP_OPEN: i32 = 9 * 60;
+pub const SHOP_CLOSE: i32 = 17 * 60;
+
+// Days in each season
+const SEASON_LENGTH: i32 = 28;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    // The first day of a run is a Monday
+    pub fn of(day: i32) -> Weekday {
+        match day % 7 {
+            0 => Weekday::Monday,
+            1 => Weekday::Tuesday,
+            2 => Weekday::Wednesday,
+            3 => Weekday::Thursday,
+            4 => Weekday::Friday,
+            5 => Weekday::Saturday,
+            _ => Weekday::Sunday,
+        }
+    }
+}
+
+impl fmt::Display for Weekday {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Weekday::Monday => "Mon",
+            Weekday::Tuesday => "Tue",
+            Weekday::Wednesday => "Wed",
+            Weekday::Thursday => "Thu",
+            Weekday::Friday => "Fri",
+            Weekday::Saturday => "Sat",
+            Weekday::Sunday => "Sun",
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    pub fn of(day: i32) -> Season {
+        match (day / SEASON_LENGTH) % 4 {
+            0 => Season::Spring,
+            1 => Season::Summer,
+            2 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+}
+
+impl fmt::Display for Season {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Season::Spring => "Spring",
+            Season::Summer => "Summer",
+            Season::Autumn => "Autumn",
+            Season::Winter => "Winter",
+        })
+    }
+}
+
+// Things that take the smith's time
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    Shop,
+    Forge,
+    Hammer,
+    Quench,
+    Grind,
+    Assemble,
+    Rack,
+}
+
+impl Activity {
+    pub fn minutes(&self) -> i32 {
+        match self {
+            Activity::Shop => 15,
+            Activity::Forge => 10,
+            Activity::Hammer => 240,
+            Activity::Quench => 15,
+            Activity::Grind => 60,
+            Activity::Assemble => 30,
+            Activity::Rack => 5,
+        }
+    }
+}
+
+// "09:30"
+pub fn format_time(minutes: i32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+// Customers only come by in opening hours, and never on a Sunday
+pub fn shop_open(day: i32, minutes: i32) -> bool {
+    Weekday::of(day) != Weekday::Sunday && (SHOP_OPEN..SHOP_CLOSE).contains(&minutes)
+}
+
+// "Mon 09:30  Day 1, Spring  Open"
+pub fn clock_text(state: &GameState) -> String {
+    let open = if shop_open(state.day, state.time) {
+        "Open"
+    } else {
+        "Closed"
+    };
+    format!(
+        "{} {}  Day {}, {}  {}",
+        Weekday::of(state.day),
+        format_time(state.time),
+        state.day + 1,
+        Season::of(state.day),
+        open
+    )
+}
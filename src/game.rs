@@ -1,14 +1,28 @@
+use crate::calendar::*;
+use crate::input::*;
+use crate::settings::*;
+use crate::toast::*;
+
+use rand::Rng;
 use sdl2::image;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
-use sdl2::EventPump;
+
+use std::fmt;
+use std::fs;
+
+// Everything is laid out for a screen this size, then scaled to fit the window
+pub const SCREEN_WIDTH: u32 = 600;
+pub const SCREEN_HEIGHT: u32 = 480;
 
 pub struct Game {
     pub sdl_context: sdl2::Sdl,
     pub image_context: sdl2::image::Sdl2ImageContext,
-    pub ttf: sdl2::ttf::Sdl2TtfContext,
+    pub ttf: &'static sdl2::ttf::Sdl2TtfContext,
     pub canvas: Canvas<Window>,
-    pub event_pump: EventPump,
+    pub input: Input,
+    pub settings: Settings,
+    pub toasts: Toasts,
     pub state: GameState,
 }
 
@@ -20,40 +34,78 @@ impl Game {
         // Start sdl2 image
         let image_context = image::init(image::InitFlag::PNG).unwrap();
 
-        // Start sdl2 ttf
-        let ttf = sdl2::ttf::init().unwrap();
+        // Start sdl2 ttf. It lives as long as the game does, and being static
+        // lets screens hold fonts while they change the rest of the game
+        let ttf = Box::leak(Box::new(sdl2::ttf::init().unwrap()));
+
+        // Broken config files fall back to the defaults, the player is told
+        // once the game is up
+        let mut load_errors = Vec::new();
+
+        // Saved settings decide how the window is made
+        let settings = Settings::load().unwrap_or_else(|e| {
+            load_errors.push(e);
+            Settings::default()
+        });
 
         // Get window
         let window = video_subsystem
-            .window("rust-sdl2 demo", 600, 480)
+            .window("Smithy", SCREEN_WIDTH, SCREEN_HEIGHT)
             .position_centered()
+            .resizable()
             .build()
             .unwrap();
 
         // Get canvas
-        let canvas: Canvas<Window> = window.into_canvas().build().unwrap();
+        let mut builder = window.into_canvas();
+        if settings.vsync {
+            builder = builder.present_vsync();
+        }
+        let mut canvas: Canvas<Window> = builder.build().unwrap();
+        canvas
+            .set_logical_size(SCREEN_WIDTH, SCREEN_HEIGHT)
+            .unwrap();
 
         // Set up event pump
         let event_pump = sdl_context.event_pump().unwrap();
 
-        Game {
+        // Controllers are opened as they're plugged in
+        let controller_subsystem = sdl_context.game_controller().unwrap();
+
+        let bindings = Bindings::load().unwrap_or_else(|e| {
+            load_errors.push(e);
+            Bindings::default()
+        });
+        let input = Input::new(event_pump, controller_subsystem, bindings);
+
+        // The ledger carries on from earlier runs, this one comes after them
+        let mut state = GameState::new();
+        state.ledger = load_ledger().unwrap_or_else(|e| {
+            load_errors.push(e);
+            Vec::new()
+        });
+        state.run = state.ledger.iter().map(|t| t.run + 1).max().unwrap_or(0);
+
+        let mut game = Game {
             sdl_context,
             image_context,
             ttf,
             canvas,
-            event_pump,
-            state: GameState::new(),
+            input,
+            settings,
+            toasts: Toasts::new(ttf),
+            state,
+        };
+
+        for e in load_errors {
+            game.toasts
+                .push(&format!("{}, using defaults", e), Severity::Error);
         }
-    }
-}
 
-pub enum MenuLevel {
-    Main,
-    Game,
-    Furnace,
-    Anvil,
-    Inventory,
-    Shop,
+        // Window mode, size and scaling
+        apply_settings(&mut game, None);
+        game
+    }
 }
 
 pub enum Temp {
@@ -62,7 +114,7 @@ pub enum Temp {
     Under,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Material {
     Iron,
     Steel,
@@ -72,15 +124,6 @@ pub enum Material {
 }
 
 impl Material {
-    pub fn to_string(&self) -> String {
-        match self {
-            Material::Iron => String::from("Iron"),
-            Material::Steel => String::from("Steel"),
-            Material::Bronze => String::from("Bronze"),
-            Material::Silver => String::from("Silver"),
-            Material::Gold => String::from("Gold"),
-        }
-    }
     pub fn base_value(&self) -> i32 {
         match self {
             Material::Iron => 100,
@@ -90,27 +133,135 @@ impl Material {
             Material::Gold => 500,
         }
     }
+    // Store price of a single unit, base value is for a bar's worth
+    pub fn unit_price(&self) -> i32 {
+        self.base_value() / BAR_WEIGHT
+    }
+    // Temperature a crucible has to reach to smelt into this metal
+    pub fn melt_temp(&self) -> i32 {
+        match self {
+            Material::Iron => 2800,
+            Material::Steel => 2500,
+            Material::Bronze => 1700,
+            Material::Silver => 1760,
+            Material::Gold => 1950,
+        }
+    }
+    // Lowest temperature the metal can be hardened from, if it hardens at all
+    pub fn harden_temp(&self) -> Option<i32> {
+        match self {
+            Material::Iron => Some(1600),
+            Material::Steel => Some(1400),
+            _ => None,
+        }
+    }
+    // Best range to pull the metal out of the quench at
+    pub fn quench_range(&self) -> (i32, i32) {
+        match self {
+            Material::Iron => (350, 550),
+            _ => (300, 500),
+        }
+    }
+    // Percent of value gained from a perfect quench
+    pub fn quench_bonus(&self) -> i32 {
+        match self {
+            Material::Iron => 10,
+            Material::Steel => 30,
+            _ => 0,
+        }
+    }
+    // Forge temperature range for tempering after a quench
+    pub fn temper_range(&self) -> (i32, i32) {
+        match self {
+            Material::Iron => (500, 700),
+            _ => (400, 600),
+        }
+    }
 }
 
-#[derive(Clone, Copy)]
+impl fmt::Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Material::Iron => "Iron",
+            Material::Steel => "Steel",
+            Material::Bronze => "Bronze",
+            Material::Silver => "Silver",
+            Material::Gold => "Gold",
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Form {
     Bar,
     Spear,
     Axe,
     Hammer,
     Sword,
+    Crucible,
+    Scrap,
 }
 
 impl Form {
-    pub fn to_string(&self) -> String {
+    // Units of metal it takes to make
+    pub fn weight(&self) -> i32 {
+        match self {
+            Form::Spear => 6,
+            Form::Axe => 8,
+            Form::Hammer => 10,
+            Form::Sword => 12,
+            Form::Bar | Form::Crucible | Form::Scrap => BAR_WEIGHT,
+        }
+    }
+    // Work needed to finish the form in a metal, in order. Only metals
+    // that gain from a quench go through the tub and get tempered
+    pub fn recipe(&self, material: Material) -> Vec<StepKind> {
+        use StepKind::*;
+
+        let hardens = material.quench_bonus() > 0;
+        let mut steps = match self {
+            Form::Bar | Form::Crucible | Form::Scrap => return vec![],
+            Form::Spear => vec![Hammer, Hammer],
+            Form::Axe | Form::Hammer => vec![Hammer, Hammer, Quench],
+            Form::Sword => vec![Hammer, Hammer, Hammer, Quench, Temper],
+        };
+        if !hardens {
+            steps.retain(|x| *x != Quench && *x != Temper);
+        }
+        steps.push(Grind);
+        steps.push(Assemble);
+        steps
+    }
+    // Parts that have to be fitted when assembling
+    pub fn parts(&self) -> Vec<Part> {
         match self {
-            Form::Bar => String::from("Bar"),
-            Form::Spear => String::from("Spear"),
-            Form::Axe => String::from("Axe"),
-            Form::Hammer => String::from("Hammer"),
-            Form::Sword => String::from("Sword"),
+            Form::Bar | Form::Crucible | Form::Scrap => vec![],
+            Form::Spear | Form::Axe => vec![Part::Haft],
+            Form::Hammer => vec![Part::WoodHandle],
+            Form::Sword => vec![Part::Guard, Part::LeatherGrip, Part::Pommel],
         }
     }
+    // Parts that can be fitted for extra value
+    pub fn optional_parts(&self) -> Vec<Part> {
+        match self {
+            Form::Axe | Form::Hammer => vec![Part::LeatherGrip],
+            _ => vec![],
+        }
+    }
+}
+
+impl fmt::Display for Form {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Form::Bar => "Bar",
+            Form::Crucible => "Crucible",
+            Form::Scrap => "Scrap",
+            Form::Spear => "Spear",
+            Form::Axe => "Axe",
+            Form::Hammer => "Hammer",
+            Form::Sword => "Sword",
+        })
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -118,25 +269,539 @@ pub enum Location {
     Storage,
     Forge,
     Anvil,
+    Rack,
+    Tub,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Location::Storage => "",
+            Location::Forge => "<Forge>",
+            Location::Anvil => "<Anvil>",
+            Location::Rack => "<Rack>",
+            Location::Tub => "<Tub>",
+        })
+    }
+}
+
+// How well an item's surface has been ground and polished
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Finish {
+    Rough,
+    Ground,
+    Polished,
+    Mirror,
+}
+
+impl Finish {
+    // Percent added to an item's value
+    pub fn bonus(&self) -> i32 {
+        match self {
+            Finish::Rough => 0,
+            Finish::Ground => 5,
+            Finish::Polished => 15,
+            Finish::Mirror => 25,
+        }
+    }
+}
+
+impl fmt::Display for Finish {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Finish::Rough => "Rough",
+            Finish::Ground => "Ground",
+            Finish::Polished => "Polished",
+            Finish::Mirror => "Mirror",
+        })
+    }
+}
+
+// Raw ingredients smelted into bars in a crucible
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Ore {
+    Copper,
+    Tin,
+    Iron,
+    Charcoal,
 }
 
-impl Location {
-    pub fn to_string(&self) -> String {
+// Weight of metal in one bar
+pub const BAR_WEIGHT: i32 = 8;
+
+// Units of ore a crucible holds, one unit of ore makes one unit of metal
+pub const CRUCIBLE_SIZE: usize = BAR_WEIGHT as usize;
+
+impl Ore {
+    pub fn all() -> Vec<Ore> {
+        vec![Ore::Copper, Ore::Tin, Ore::Iron, Ore::Charcoal]
+    }
+    pub fn price(&self) -> i32 {
         match self {
-            Location::Storage => String::new(),
-            Location::Forge => String::from("<Forge>"),
-            Location::Anvil => String::from("<Anvil>"),
+            Ore::Copper => 8,
+            Ore::Tin => 12,
+            Ore::Iron => 10,
+            Ore::Charcoal => 3,
         }
     }
 }
 
+impl fmt::Display for Ore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Ore::Copper => "Copper",
+            Ore::Tin => "Tin",
+            Ore::Iron => "Iron Ore",
+            Ore::Charcoal => "Charcoal",
+        })
+    }
+}
+
+// Work out what a crucible of ore will smelt into and how pure it'll be.
+// Bronze wants one part tin in eight, iron a little charcoal and steel a
+// quarter charcoal; every percent off the ideal ratio costs 4 purity.
+pub fn smelt(charge: &[Ore]) -> Result<(Material, i32), String> {
+    let count = |o: Ore| charge.iter().filter(|x| **x == o).count() as i32;
+    let (copper, tin, iron, charcoal) = (
+        count(Ore::Copper),
+        count(Ore::Tin),
+        count(Ore::Iron),
+        count(Ore::Charcoal),
+    );
+    let total = charge.len() as i32;
+
+    if total == 0 {
+        return Err(String::from("Crucible is empty"));
+    }
+    if copper > 0 && iron > 0 {
+        return Err(String::from("Copper and iron don't mix"));
+    }
+
+    let (material, pct, target) = if copper > 0 {
+        if tin == 0 {
+            return Err(String::from("Copper needs tin for bronze"));
+        }
+        (Material::Bronze, tin * 100 / total, 12)
+    } else if iron > 0 {
+        let pct = charcoal * 100 / total;
+        if pct <= 15 {
+            (Material::Iron, pct, 12)
+        } else {
+            (Material::Steel, pct, 25)
+        }
+    } else {
+        return Err(String::from("Nothing to smelt"));
+    };
+
+    // Anything that isn't part of the recipe is an impurity
+    let stray = match material {
+        Material::Bronze => charcoal,
+        _ => tin,
+    };
+    let off = (pct - target).abs() + stray * 100 / total;
+
+    Ok((material, (100 - 4 * off).clamp(0, 100)))
+}
+
+// Bought components fitted to a blade or head at the desk
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Part {
+    WoodHandle,
+    Haft,
+    LeatherGrip,
+    Guard,
+    Pommel,
+}
+
+impl Part {
+    pub fn all() -> Vec<Part> {
+        vec![
+            Part::WoodHandle,
+            Part::Haft,
+            Part::LeatherGrip,
+            Part::Guard,
+            Part::Pommel,
+        ]
+    }
+    pub fn price(&self) -> i32 {
+        match self {
+            Part::WoodHandle => 20,
+            Part::Haft => 30,
+            Part::LeatherGrip => 25,
+            Part::Guard => 60,
+            Part::Pommel => 40,
+        }
+    }
+    // What the part adds to a finished item
+    pub fn value(&self) -> i32 {
+        self.price() * 2
+    }
+}
+
+impl fmt::Display for Part {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Part::WoodHandle => "Wood Handle",
+            Part::Haft => "Haft",
+            Part::LeatherGrip => "Leather Grip",
+            Part::Guard => "Guard",
+            Part::Pommel => "Pommel",
+        })
+    }
+}
+
+// Anything the shop sells
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Goods {
+    Metal(Material),
+    Ore(Ore),
+    Part(Part),
+}
+
+impl Goods {
+    pub fn all() -> Vec<Goods> {
+        let mut goods: Vec<Goods> = vec![
+            Material::Iron,
+            Material::Steel,
+            Material::Bronze,
+            Material::Silver,
+            Material::Gold,
+        ]
+        .into_iter()
+        .map(Goods::Metal)
+        .collect();
+        goods.extend(Ore::all().into_iter().map(Goods::Ore));
+        goods.extend(Part::all().into_iter().map(Goods::Part));
+        goods
+    }
+    pub fn unit_price(&self) -> i32 {
+        match self {
+            Goods::Metal(m) => m.unit_price(),
+            Goods::Ore(o) => o.price(),
+            Goods::Part(p) => p.price(),
+        }
+    }
+    // Storage taken up by one unit
+    pub fn storage(&self) -> i32 {
+        match self {
+            Goods::Metal(_) | Goods::Ore(_) => 1,
+            Goods::Part(_) => 0,
+        }
+    }
+    // How many to offer at once
+    pub fn default_quantity(&self) -> i32 {
+        match self {
+            Goods::Metal(_) => BAR_WEIGHT,
+            Goods::Ore(_) | Goods::Part(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for Goods {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Goods::Metal(m) => write!(f, "{}", m),
+            Goods::Ore(o) => write!(f, "{}", o),
+            Goods::Part(p) => write!(f, "{}", p),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TransactionKind {
+    Purchase,
+    Sale,
+    Upkeep,
+    Loan,
+    Repayment,
+}
+
+impl TransactionKind {
+    pub fn all() -> Vec<TransactionKind> {
+        vec![
+            TransactionKind::Purchase,
+            TransactionKind::Sale,
+            TransactionKind::Upkeep,
+            TransactionKind::Loan,
+            TransactionKind::Repayment,
+        ]
+    }
+    // Name used in the ledger file
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionKind::Purchase => "purchase",
+            TransactionKind::Sale => "sale",
+            TransactionKind::Upkeep => "upkeep",
+            TransactionKind::Loan => "loan",
+            TransactionKind::Repayment => "repayment",
+        }
+    }
+    pub fn from_name(s: &str) -> Option<TransactionKind> {
+        TransactionKind::all().into_iter().find(|k| k.name() == s)
+    }
+    // Borrowed money isn't earned and paying it back isn't spending
+    pub fn is_trade(&self) -> bool {
+        !matches!(self, TransactionKind::Loan | TransactionKind::Repayment)
+    }
+}
+
+impl fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TransactionKind::Purchase => "Purchase",
+            TransactionKind::Sale => "Sale",
+            TransactionKind::Upkeep => "Upkeep",
+            TransactionKind::Loan => "Loan",
+            TransactionKind::Repayment => "Repayment",
+        })
+    }
+}
+
+// Most the bank will lend, owing more than this is bankruptcy
+pub const LOAN_LIMIT: i32 = 500;
+
+// Loans are taken out and paid back in steps of this much
+pub const LOAN_STEP: i32 = 100;
+
+// Percent of the debt charged as interest each day
+pub const INTEREST: i32 = 5;
+
+// A line in the ledger, amount is negative for money going out
+pub struct Transaction {
+    // Every start of the game is a new run, the ledger keeps them all
+    pub run: i32,
+    pub day: i32,
+    pub kind: TransactionKind,
+    pub description: String,
+    pub amount: i32,
+}
+
+// Ledger file: a transaction a line, "run day kind amount description"
+pub fn parse_ledger(text: &str) -> Result<Vec<Transaction>, String> {
+    let mut ledger = Vec::new();
+    for (i, raw) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = raw.trim();
+
+        // Skip blanks + comments
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // The description is the rest of the line, spaces and all
+        let words: Vec<&str> = line.splitn(5, ' ').collect();
+        if words.len() < 4 {
+            return Err(format!("line {}: expected run day kind amount", line_no));
+        }
+        let number = |w: &str| {
+            w.parse::<i32>()
+                .map_err(|_| format!("line {}: bad number '{}'", line_no, w))
+        };
+        let kind = TransactionKind::from_name(words[2])
+            .ok_or(format!("line {}: unknown kind '{}'", line_no, words[2]))?;
+        ledger.push(Transaction {
+            run: number(words[0])?,
+            day: number(words[1])?,
+            kind,
+            amount: number(words[3])?,
+            description: String::from(*words.get(4).unwrap_or(&"")),
+        });
+    }
+    Ok(ledger)
+}
+
+pub fn ledger_text(ledger: &[Transaction]) -> String {
+    let mut s = String::from("# Smithy ledger\n");
+    for t in ledger {
+        s.push_str(&format!(
+            "{} {} {} {} {}\n",
+            t.run,
+            t.day,
+            t.kind.name(),
+            t.amount,
+            t.description.replace('\n', " ")
+        ));
+    }
+    s
+}
+
+// Books from earlier runs, empty if there are none yet
+pub fn load_ledger() -> Result<Vec<Transaction>, String> {
+    match fs::read_to_string(config_dir().join("ledger.txt")) {
+        Ok(text) => parse_ledger(&text).map_err(|e| format!("ledger.txt {}", e)),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+pub fn save_ledger(ledger: &[Transaction]) -> Result<(), String> {
+    let dir = config_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+    let path = dir.join("ledger.txt");
+    fs::write(&path, ledger_text(ledger)).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Reputation for crafting a masterpiece, on top of what selling it brings
+pub const MASTERPIECE_REPUTATION: i32 = 5;
+
+// Most orders waiting at once
+pub const MAX_ORDERS: usize = 3;
+
+// Reputation for an order handed over in time, and lost for one that isn't
+pub const ORDER_REPUTATION: i32 = 2;
+
+// A customer wants a finished item by the end of a given day
+#[derive(Clone)]
+pub struct Order {
+    pub form: Form,
+    pub material: Material,
+    // Least finish they'll take
+    pub finish: Finish,
+    // Extra they want fitted, if any
+    pub part: Option<Part>,
+    // Paid on delivery
+    pub reward: i32,
+    // Last day it can be handed over
+    pub due: i32,
+}
+
+impl Order {
+    // Something the workshop could make, due a few days from now
+    pub fn random(day: i32) -> Order {
+        let mut rng = rand::thread_rng();
+        let forms = [Form::Spear, Form::Axe, Form::Hammer, Form::Sword];
+        let materials = [Material::Iron, Material::Steel, Material::Bronze];
+        let finishes = [Finish::Rough, Finish::Ground, Finish::Polished];
+
+        let form = forms[rng.gen_range(0..forms.len())];
+        let material = materials[rng.gen_range(0..materials.len())];
+        let finish = finishes[rng.gen_range(0..finishes.len())];
+
+        // Some want an extra fitted, when the form takes one
+        let extras = form.optional_parts();
+        let part = if !extras.is_empty() && rng.gen_bool(0.5) {
+            Some(extras[rng.gen_range(0..extras.len())])
+        } else {
+            None
+        };
+
+        // Half as much again as the metal is worth, more for a better finish
+        let metal = material.base_value() * form.weight() / BAR_WEIGHT;
+        let extra = part.map_or(0, |x| x.value());
+        Order {
+            form,
+            material,
+            finish,
+            part,
+            reward: metal * (150 + 2 * finish.bonus()) / 100 + extra,
+            due: day + rng.gen_range(3..=5),
+        }
+    }
+    // Why an item won't do, None if it can be handed over
+    pub fn refusal(&self, p: &Product) -> Option<String> {
+        if p.form != self.form || p.material != self.material {
+            return Some(format!("They want a {} {}", self.material, self.form));
+        }
+        if !p.is_finished() {
+            return Some(String::from("It isn't finished yet"));
+        }
+        if p.finish < self.finish {
+            return Some(format!("It needs a {} finish or better", self.finish));
+        }
+        if let Some(part) = self.part {
+            if !p.parts.contains(&part) {
+                return Some(format!("It needs a {} fitted", part));
+            }
+        }
+        None
+    }
+}
+
+// "Polished Iron Axe with Leather Grip", the finish left off when any
+// will do
+impl fmt::Display for Order {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.finish != Finish::Rough {
+            write!(f, "{} ", self.finish)?;
+        }
+        write!(f, "{} {}", self.material, self.form)?;
+        if let Some(part) = self.part {
+            write!(f, " with {}", part)?;
+        }
+        Ok(())
+    }
+}
+
+// Kinds of work that can be done to an item
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+    Heat,
+    Hammer,
+    Quench,
+    Temper,
+    Grind,
+    Assemble,
+}
+
+impl fmt::Display for StepKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            StepKind::Heat => "Heat",
+            StepKind::Hammer => "Hammer",
+            StepKind::Quench => "Quench",
+            StepKind::Temper => "Temper",
+            StepKind::Grind => "Grind",
+            StepKind::Assemble => "Assemble",
+        })
+    }
+}
+
+impl StepKind {
+    // Steps that can be left out, moving straight on to the next one
+    pub fn optional(&self) -> bool {
+        matches!(self, StepKind::Temper)
+    }
+}
+
+// A piece of work done to an item and how well it went
 #[derive(Clone, Copy)]
+pub enum Step {
+    Heat,
+    Hammer(i32),
+    Quench(i32),
+    Temper(bool),
+    Grind(Finish),
+    Assemble,
+}
+
+impl Step {
+    pub fn kind(&self) -> StepKind {
+        match self {
+            Step::Heat => StepKind::Heat,
+            Step::Hammer(_) => StepKind::Hammer,
+            Step::Quench(_) => StepKind::Quench,
+            Step::Temper(_) => StepKind::Temper,
+            Step::Grind(_) => StepKind::Grind,
+            Step::Assemble => StepKind::Assemble,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Product {
     pub material: Material,
     pub form: Form,
     pub location: Location,
     pub value: i32,
     pub temp: i32,
+    pub finish: Finish,
+    // 0-100, how well the item was hardened
+    pub hardness: i32,
+    pub history: Vec<Step>,
+    pub parts: Vec<Part>,
+    // 0-100, how clean the metal is, bought bars are pure
+    pub purity: i32,
+    pub weight: i32,
 }
 
 impl Product {
@@ -149,93 +814,880 @@ impl Product {
             location: Location::Storage,
             value: v,
             temp: 70,
+            finish: Finish::Rough,
+            hardness: 0,
+            history: vec![],
+            parts: vec![],
+            purity: 100,
+            weight: BAR_WEIGHT,
         }
     }
-    pub fn to_string(&self) -> String {
-        let mut s: String = String::from(self.material.to_string());
-        s.push_str(" ");
+    // Finish, material and form, e.g. "Polished Steel Sword"
+    pub fn name(&self) -> String {
+        let mut s: String = String::new();
+        if self.finish != Finish::Rough {
+            s.push_str(&self.finish.to_string());
+            s.push(' ');
+        }
+        s.push_str(&self.material.to_string());
+        s.push(' ');
         s.push_str(&self.form.to_string());
-        s.push_str(" ");
-        s.push_str(&self.location.to_string());
-        s.push_str(": ");
-        s.push_str(&self.value.to_string());
-        s.push_str("$");
 
         s
     }
+    pub fn recipe(&self) -> Vec<StepKind> {
+        self.form.recipe(self.material)
+    }
+    // How many steps of the form's recipe have been done, counting
+    // optional steps that were skipped
+    pub fn steps_done(&self) -> usize {
+        let recipe = self.recipe();
+        let mut done = 0;
+
+        for step in &self.history {
+            let kind = step.kind();
+            if done < recipe.len() && kind == recipe[done] {
+                done += 1;
+            } else if done + 1 < recipe.len() && recipe[done].optional() && kind == recipe[done + 1]
+            {
+                done += 2;
+            }
+        }
+        done
+    }
+    pub fn next_step(&self) -> Option<StepKind> {
+        self.recipe().get(self.steps_done()).copied()
+    }
+    // Whether a step can be done now, skipping an optional one if need be
+    pub fn due(&self, kind: StepKind) -> bool {
+        let recipe = self.recipe();
+        let done = self.steps_done();
+        match recipe.get(done) {
+            Some(next) if *next == kind => true,
+            Some(next) if next.optional() => recipe.get(done + 1) == Some(&kind),
+            _ => false,
+        }
+    }
+    pub fn is_finished(&self) -> bool {
+        !self.recipe().is_empty() && self.next_step().is_none()
+    }
+    // Flawless work: mirror finish, pure metal and a good hardening
+    pub fn is_masterpiece(&self) -> bool {
+        let hard_enough = match self.material.harden_temp() {
+            Some(_) => self.hardness >= 80,
+            None => true,
+        };
+        self.is_finished() && self.finish == Finish::Mirror && self.purity >= 90 && hard_enough
+    }
+    // How selling it changes the smith's name. Fine work builds it, half
+    // done or overtempered work costs it, raw metal doesn't count
+    pub fn reputation(&self) -> i32 {
+        if self.recipe().is_empty() {
+            return 0;
+        }
+        if !self.is_finished() {
+            return -1;
+        }
+        if self.is_masterpiece() {
+            return 3;
+        }
+        let botched = self
+            .history
+            .iter()
+            .any(|x| matches!(x, Step::Temper(false)));
+        if botched {
+            -1
+        } else {
+            1
+        }
+    }
+    // What's been done and what's left, e.g. "Heat, Hammer | Next: Quench"
+    pub fn pipeline(&self) -> String {
+        let done: Vec<String> = self.history.iter().map(|x| x.kind().to_string()).collect();
+        let mut s = if done.is_empty() {
+            String::from("New")
+        } else {
+            done.join(", ")
+        };
+
+        let left: Vec<String> = self.recipe()[self.steps_done()..]
+            .iter()
+            .map(|x| {
+                if x.optional() {
+                    format!("{} (optional)", x)
+                } else {
+                    x.to_string()
+                }
+            })
+            .collect();
+        if !left.is_empty() {
+            s.push_str(" | Next: ");
+            s.push_str(&left.join(", "));
+        } else if self.is_finished() {
+            s.push_str(" | Done");
+        }
+
+        if !self.parts.is_empty() {
+            let parts: Vec<String> = self.parts.iter().map(|x| x.to_string()).collect();
+            s.push_str(" | Parts: ");
+            s.push_str(&parts.join(", "));
+        }
+        s
+    }
+    // Work out the value from everything done to the item
+    pub fn appraise(&mut self) {
+        let hammers: Vec<i32> = self
+            .history
+            .iter()
+            .filter_map(|x| match x {
+                Step::Hammer(score) => Some(*score),
+                _ => None,
+            })
+            .collect();
+
+        // Impure metal is worth less whatever is done to it
+        let purity = 50 + self.purity / 2;
+
+        if hammers.is_empty() {
+            self.value = self.material.base_value() * self.weight / BAR_WEIGHT * purity / 100;
+
+            // Scrap has to be melted again before it's any use
+            if let Form::Scrap = self.form {
+                self.value /= 2;
+            }
+            return;
+        }
+
+        // Shaping sets the base, bigger pieces are worth more
+        let base = hammers.iter().sum::<i32>() / hammers.len() as i32;
+        let base = base * self.weight / BAR_WEIGHT;
+        let mut bonus = self.finish.bonus();
+        bonus += self.material.quench_bonus() * self.hardness / 100;
+        // A good temper adds a third again of what the hardening gave
+        for step in &self.history {
+            match step {
+                Step::Temper(true) => bonus += self.material.quench_bonus() * self.hardness / 300,
+                Step::Temper(false) => bonus -= 10,
+                _ => (),
+            }
+        }
+
+        self.value = base * (100 + bonus) / 100;
+        self.value = self.value * purity / 100;
+        self.value += self.parts.iter().map(|x| x.value()).sum::<i32>();
+
+        // Half done work doesn't sell as well
+        if !self.is_finished() {
+            self.value = self.value * 3 / 4;
+        }
+    }
     pub fn temp_val(&self) -> Temp {
+        // Crucibles are ready once they're hot enough to melt
+        if let Form::Crucible = self.form {
+            if self.temp < self.material.melt_temp() {
+                return Temp::Under;
+            }
+            return Temp::Perfect;
+        }
+
+        // Hardened items are only ever heated again to temper them
+        if self.next_step() == Some(StepKind::Temper) {
+            let (lo, hi) = self.material.temper_range();
+            if self.temp < lo {
+                return Temp::Under;
+            } else if self.temp > hi {
+                return Temp::Over;
+            } else {
+                return Temp::Perfect;
+            }
+        }
+
         match self.material {
             Material::Iron => {
                 if self.temp < 2400 {
-                    return Temp::Under;
+                    Temp::Under
                 } else if self.temp > 2600 {
-                    return Temp::Over;
+                    Temp::Over
                 } else {
-                    return Temp::Perfect;
+                    Temp::Perfect
                 }
             }
             Material::Steel => {
                 if self.temp < 2100 {
-                    return Temp::Under;
+                    Temp::Under
                 } else if self.temp > 2300 {
-                    return Temp::Over;
+                    Temp::Over
                 } else {
-                    return Temp::Perfect;
+                    Temp::Perfect
                 }
             }
             Material::Bronze => {
                 if self.temp < 1100 {
-                    return Temp::Under;
+                    Temp::Under
                 } else if self.temp > 1300 {
-                    return Temp::Over;
+                    Temp::Over
                 } else {
-                    return Temp::Perfect;
+                    Temp::Perfect
                 }
             }
             Material::Silver => {
                 if self.temp < 1050 {
-                    return Temp::Under;
+                    Temp::Under
                 } else if self.temp > 1250 {
-                    return Temp::Over;
+                    Temp::Over
                 } else {
-                    return Temp::Perfect;
+                    Temp::Perfect
                 }
             }
             Material::Gold => {
                 if self.temp < 1150 {
-                    return Temp::Under;
+                    Temp::Under
                 } else if self.temp > 1350 {
-                    return Temp::Over;
+                    Temp::Over
                 } else {
-                    return Temp::Perfect;
+                    Temp::Perfect
                 }
             }
         }
     }
 }
 
+impl fmt::Display for Product {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ", self.name())?;
+        let recipe = self.recipe();
+        if !recipe.is_empty() {
+            write!(f, "[{}/{}] ", self.steps_done(), recipe.len())?;
+        }
+        write!(f, "({}u) {}: {}$", self.weight, self.location, self.value)
+    }
+}
+
+// Stations that can be added to the workshop
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Expansion {
+    SecondForge,
+    QuenchTub,
+    Grindstone,
+    DisplayRack,
+    Chest,
+    SecondChest,
+}
+
+impl Expansion {
+    pub fn all() -> Vec<Expansion> {
+        vec![
+            Expansion::SecondForge,
+            Expansion::QuenchTub,
+            Expansion::Grindstone,
+            Expansion::DisplayRack,
+            Expansion::Chest,
+            Expansion::SecondChest,
+        ]
+    }
+    pub fn from_name(s: &str) -> Option<Expansion> {
+        match s {
+            "second_forge" => Some(Expansion::SecondForge),
+            "quench_tub" => Some(Expansion::QuenchTub),
+            "grindstone" => Some(Expansion::Grindstone),
+            "display_rack" => Some(Expansion::DisplayRack),
+            "chest" => Some(Expansion::Chest),
+            "second_chest" => Some(Expansion::SecondChest),
+            _ => None,
+        }
+    }
+    pub fn price(&self) -> i32 {
+        match self {
+            Expansion::SecondForge => 400,
+            Expansion::QuenchTub => 150,
+            Expansion::Grindstone => 200,
+            Expansion::DisplayRack => 250,
+            Expansion::Chest => 100,
+            Expansion::SecondChest => 150,
+        }
+    }
+}
+
+impl fmt::Display for Expansion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Expansion::SecondForge => "Second Forge",
+            Expansion::QuenchTub => "Quench Tub",
+            Expansion::Grindstone => "Grindstone",
+            Expansion::DisplayRack => "Display Rack",
+            Expansion::Chest => "Storage Chest",
+            Expansion::SecondChest => "Second Chest",
+        })
+    }
+}
+
+// Tiered upgrades bought from the desk
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum UpgradeKind {
+    ForgeSlots,
+    Storage,
+    Anvil,
+    Bellows,
+    Tools,
+}
+
+// What has to be owned before a tier can be bought
+pub enum Requirement {
+    Upgrade(UpgradeKind, i32),
+    Expansion(Expansion),
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Requirement::Upgrade(k, t) => write!(f, "{} {}", k, t),
+            Requirement::Expansion(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl UpgradeKind {
+    pub fn all() -> Vec<UpgradeKind> {
+        vec![
+            UpgradeKind::ForgeSlots,
+            UpgradeKind::Storage,
+            UpgradeKind::Anvil,
+            UpgradeKind::Bellows,
+            UpgradeKind::Tools,
+        ]
+    }
+    pub fn max_tier(&self) -> i32 {
+        3
+    }
+    // Price of buying the given tier, doubles every tier
+    pub fn price(&self, tier: i32) -> i32 {
+        let base = match self {
+            UpgradeKind::ForgeSlots => 200,
+            UpgradeKind::Storage => 100,
+            UpgradeKind::Anvil => 250,
+            UpgradeKind::Bellows => 150,
+            UpgradeKind::Tools => 100,
+        };
+        base * (1 << (tier - 1))
+    }
+    pub fn requirement(&self, tier: i32) -> Option<Requirement> {
+        match (self, tier) {
+            (UpgradeKind::ForgeSlots, 2) => Some(Requirement::Upgrade(UpgradeKind::Bellows, 1)),
+            (UpgradeKind::ForgeSlots, 3) => Some(Requirement::Expansion(Expansion::SecondForge)),
+            (UpgradeKind::Storage, 2) => Some(Requirement::Expansion(Expansion::Chest)),
+            (UpgradeKind::Storage, 3) => Some(Requirement::Expansion(Expansion::SecondChest)),
+            (UpgradeKind::Anvil, 2) => Some(Requirement::Upgrade(UpgradeKind::Tools, 1)),
+            (UpgradeKind::Anvil, 3) => Some(Requirement::Upgrade(UpgradeKind::Tools, 2)),
+            (UpgradeKind::Bellows, 3) => Some(Requirement::Upgrade(UpgradeKind::Anvil, 1)),
+            (UpgradeKind::Tools, 3) => Some(Requirement::Upgrade(UpgradeKind::Anvil, 2)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for UpgradeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            UpgradeKind::ForgeSlots => "Forge Slots",
+            UpgradeKind::Storage => "Storage",
+            UpgradeKind::Anvil => "Anvil",
+            UpgradeKind::Bellows => "Bellows",
+            UpgradeKind::Tools => "Tools",
+        })
+    }
+}
+
 pub struct Upgrades {
     pub forge_space: i32,
+    // Units of metal and ore that can be kept
     pub storage_space: i32,
+    pub expansions: Vec<Expansion>,
+    pub forge_tier: i32,
+    pub storage_tier: i32,
+    pub anvil_tier: i32,
+    pub bellows_tier: i32,
+    pub tools_tier: i32,
+}
+
+impl Upgrades {
+    pub fn has(&self, e: Expansion) -> bool {
+        self.expansions.contains(&e)
+    }
+    // Add a station and apply what it does
+    pub fn build(&mut self, e: Expansion) {
+        if self.has(e) {
+            return;
+        }
+        match e {
+            Expansion::SecondForge => self.forge_space += 1,
+            Expansion::Chest | Expansion::SecondChest => self.storage_space += 5 * BAR_WEIGHT,
+            _ => (),
+        }
+        self.expansions.push(e);
+    }
+    pub fn tier(&self, k: UpgradeKind) -> i32 {
+        match k {
+            UpgradeKind::ForgeSlots => self.forge_tier,
+            UpgradeKind::Storage => self.storage_tier,
+            UpgradeKind::Anvil => self.anvil_tier,
+            UpgradeKind::Bellows => self.bellows_tier,
+            UpgradeKind::Tools => self.tools_tier,
+        }
+    }
+    pub fn meets(&self, r: &Requirement) -> bool {
+        match r {
+            Requirement::Upgrade(k, t) => self.tier(*k) >= *t,
+            Requirement::Expansion(e) => self.has(*e),
+        }
+    }
+    // Go up a tier and apply what it does
+    pub fn buy(&mut self, k: UpgradeKind) {
+        match k {
+            UpgradeKind::ForgeSlots => {
+                self.forge_tier += 1;
+                self.forge_space += 1;
+            }
+            UpgradeKind::Storage => {
+                self.storage_tier += 1;
+                self.storage_space += 3 * BAR_WEIGHT;
+            }
+            UpgradeKind::Anvil => self.anvil_tier += 1,
+            UpgradeKind::Bellows => self.bellows_tier += 1,
+            UpgradeKind::Tools => self.tools_tier += 1,
+        }
+    }
+}
+
+// What happened over one day, for the report at the end of it
+pub struct DayStats {
+    pub crafted: i32,
+    pub best_score: Option<i32>,
+    // Reputation when the day started
+    pub reputation: i32,
+}
+
+impl DayStats {
+    pub fn new(reputation: i32) -> DayStats {
+        DayStats {
+            crafted: 0,
+            best_score: None,
+            reputation,
+        }
+    }
 }
 
 pub struct GameState {
     pub inventory: Vec<Product>,
     pub money: i32,
+    pub day: i32,
+    // Minutes after midnight
+    pub time: i32,
+    pub ledger: Vec<Transaction>,
+    // Which run this is, for telling this run's transactions from old ones
+    pub run: i32,
+    pub today: DayStats,
+    // Money owed to the bank
+    pub debt: i32,
+    // Masterpieces crafted over the whole run
+    pub masterpieces: i32,
     pub reputation: i32,
     pub upgrades: Upgrades,
+    // Bought parts waiting to be fitted
+    pub parts: Vec<Part>,
+    // Bought ore waiting to be smelted
+    pub ores: Vec<Ore>,
+    // Customers waiting on items, oldest first
+    pub orders: Vec<Order>,
 }
 
 impl GameState {
+    // Storage used by metal and ore
+    pub fn stored_weight(&self) -> i32 {
+        let metal: i32 = self.inventory.iter().map(|x| x.weight).sum();
+        metal + self.ores.len() as i32
+    }
+    // Move money and write it in the ledger
+    pub fn transact(&mut self, kind: TransactionKind, description: String, amount: i32) {
+        self.money += amount;
+        self.ledger.push(Transaction {
+            run: self.run,
+            day: self.day,
+            kind,
+            description,
+            amount,
+        });
+    }
+    // Money made and spent on a given day
+    pub fn income(&self, day: i32) -> i32 {
+        self.ledger
+            .iter()
+            .filter(|t| t.run == self.run && t.day == day && t.kind.is_trade() && t.amount > 0)
+            .map(|t| t.amount)
+            .sum()
+    }
+    pub fn expenses(&self, day: i32) -> i32 {
+        self.ledger
+            .iter()
+            .filter(|t| t.run == self.run && t.day == day && t.kind.is_trade() && t.amount < 0)
+            .map(|t| -t.amount)
+            .sum()
+    }
+    // Bills for keeping the shop open another day
+    pub fn upkeep(&self) -> Vec<(String, i32)> {
+        let u = &self.upgrades;
+        let mut costs = vec![
+            (String::from("Rent"), 15 + 5 * u.expansions.len() as i32),
+            (String::from("Fuel"), 4 * u.forge_space + 2 * u.bellows_tier),
+            (String::from("Wages"), 10),
+        ];
+        if self.debt > 0 {
+            // Rounded up so small debts still cost something
+            let interest = (self.debt * INTEREST + 99) / 100;
+            costs.push((String::from("Interest"), interest));
+        }
+        costs
+    }
+    // Pay the day's bills, the bank covers anything the till can't
+    pub fn pay_upkeep(&mut self) {
+        for (name, cost) in self.upkeep() {
+            self.transact(TransactionKind::Upkeep, name, -cost);
+        }
+        if self.money < 0 {
+            self.borrow(-self.money);
+        }
+    }
+    pub fn borrow(&mut self, amount: i32) {
+        self.debt += amount;
+        self.transact(TransactionKind::Loan, String::from("Loan"), amount);
+    }
+    pub fn repay(&mut self, amount: i32) {
+        self.debt -= amount;
+        self.transact(
+            TransactionKind::Repayment,
+            String::from("Loan repaid"),
+            -amount,
+        );
+    }
+    pub fn bankrupt(&self) -> bool {
+        self.debt > LOAN_LIMIT
+    }
+    // Money less what's owed to the bank
+    pub fn net_worth(&self) -> i32 {
+        self.money - self.debt
+    }
+    // Keep the best hammering of the day
+    pub fn record_score(&mut self, score: i32) {
+        let best = self.today.best_score.unwrap_or(score).max(score);
+        self.today.best_score = Some(best);
+    }
+    // Time taken by doing something
+    pub fn spend_time(&mut self, activity: Activity) {
+        self.time += activity.minutes();
+    }
+    // A new customer, if there's room for one
+    pub fn take_order(&mut self) -> Option<Order> {
+        if self.orders.len() >= MAX_ORDERS {
+            return None;
+        }
+        let order = Order::random(self.day);
+        self.orders.push(order.clone());
+        Some(order)
+    }
+    // Hand an item over for an order, paying for it and building a name
+    pub fn deliver(&mut self, order: usize, item: usize) {
+        let o = self.orders.remove(order);
+        self.inventory.remove(item);
+        self.transact(TransactionKind::Sale, format!("Order: {}", o), o.reward);
+        self.reputation += ORDER_REPUTATION;
+    }
+    // Drop orders due today that weren't handed over, customers talk
+    pub fn expire_orders(&mut self) -> Vec<Order> {
+        let day = self.day;
+        let (missed, kept) = self.orders.drain(..).partition(|o| o.due <= day);
+        self.orders = kept;
+        self.reputation -= ORDER_REPUTATION * missed.len() as i32;
+        missed
+    }
+    // Move on to the next day with a clean slate
+    pub fn end_day(&mut self) {
+        self.day += 1;
+        self.time = DAY_START;
+        self.today = DayStats::new(self.reputation);
+    }
+    // Add bought metal, topping up a matching cold bar if there is one
+    pub fn add_metal(&mut self, m: Material, weight: i32) {
+        let stock = self.inventory.iter_mut().find(|x| {
+            x.material == m
+                && matches!(x.form, Form::Bar)
+                && x.location == Location::Storage
+                && x.purity == 100
+                && x.temp <= 70
+        });
+
+        match stock {
+            Some(p) => {
+                p.weight += weight;
+                p.appraise();
+            }
+            None => {
+                let mut p = Product::new(m);
+                p.weight = weight;
+                p.appraise();
+                self.inventory.push(p);
+            }
+        }
+    }
     pub fn new() -> GameState {
         GameState {
             inventory: vec![],
             money: 100,
+            day: 0,
+            time: DAY_START,
+            ledger: vec![],
+            run: 0,
+            today: DayStats::new(0),
+            debt: 0,
+            masterpieces: 0,
             reputation: 0,
             upgrades: Upgrades {
                 forge_space: 1,
-                storage_space: 5,
+                storage_space: 5 * BAR_WEIGHT,
+                expansions: vec![],
+                forge_tier: 0,
+                storage_tier: 0,
+                anvil_tier: 0,
+                bellows_tier: 0,
+                tools_tier: 0,
             },
+            parts: vec![],
+            ores: vec![],
+            orders: vec![],
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ledger_round_trip() {
+        let mut state = GameState::new();
+        state.run = 2;
+        state.day = 4;
+        state.transact(TransactionKind::Sale, String::from("Sold Steel Sword"), 550);
+        state.transact(
+            TransactionKind::Purchase,
+            String::from("Bought 10u Iron"),
+            -100,
+        );
+
+        let ledger = parse_ledger(&ledger_text(&state.ledger)).unwrap();
+        assert_eq!(ledger.len(), 2);
+        assert_eq!(ledger[0].run, 2);
+        assert_eq!(ledger[0].day, 4);
+        assert!(ledger[0].kind == TransactionKind::Sale);
+        assert_eq!(ledger[0].description, "Sold Steel Sword");
+        assert_eq!(ledger[1].amount, -100);
+    }
+
+    #[test]
+    fn ledger_rejects_bad_lines() {
+        assert!(parse_ledger("0 1 sale").is_err());
+        assert!(parse_ledger("0 1 theft 50 Stolen").is_err());
+        assert!(parse_ledger("0 one sale 50 Sold").is_err());
+        assert!(parse_ledger("# comment\n\n0 1 upkeep -30").is_ok());
+    }
+
+    // A spear with every step of its recipe done
+    fn finished_spear() -> Product {
+        let mut p = Product::new(Material::Iron);
+        p.form = Form::Spear;
+        p.history = vec![
+            Step::Hammer(80),
+            Step::Hammer(80),
+            Step::Grind(Finish::Polished),
+            Step::Assemble,
+        ];
+        p
+    }
+
+    #[test]
+    fn recipes_follow_the_metal() {
+        use StepKind::*;
+
+        let quenched = [Hammer, Hammer, Quench, Grind, Assemble];
+        assert!(Form::Axe.recipe(Material::Steel) == quenched);
+        assert!(Form::Hammer.recipe(Material::Iron) == quenched);
+        assert!(Form::Axe.recipe(Material::Bronze) == [Hammer, Hammer, Grind, Assemble]);
+
+        let sword = [Hammer, Hammer, Hammer, Quench, Temper, Grind, Assemble];
+        assert!(Form::Sword.recipe(Material::Steel) == sword);
+        for m in [Material::Bronze, Material::Silver, Material::Gold] {
+            assert!(Form::Sword.recipe(m) == [Hammer, Hammer, Hammer, Grind, Assemble]);
+            assert!(Form::Spear.recipe(m) == [Hammer, Hammer, Grind, Assemble]);
+        }
+        assert!(Form::Bar.recipe(Material::Steel).is_empty());
+    }
+
+    #[test]
+    fn temper_bonus_follows_hardness() {
+        let tempered = |m: Material, hardness: i32| {
+            let mut p = Product::new(m);
+            p.form = Form::Spear;
+            p.history = vec![Step::Hammer(80), Step::Hammer(80)];
+            p.hardness = hardness;
+            p.appraise();
+            let before = p.value;
+            p.history.push(Step::Temper(true));
+            p.appraise();
+            p.value - before
+        };
+        assert_eq!(tempered(Material::Bronze, 0), 0);
+        assert_eq!(tempered(Material::Gold, 90), 0);
+        assert_eq!(tempered(Material::Steel, 0), 0);
+        assert!(tempered(Material::Steel, 90) > 0);
+    }
+
+    #[test]
+    fn tempering_can_be_skipped() {
+        let mut p = Product::new(Material::Steel);
+        p.form = Form::Sword;
+        p.history = vec![
+            Step::Hammer(80),
+            Step::Hammer(80),
+            Step::Hammer(80),
+            Step::Quench(80),
+        ];
+        assert!(p.next_step() == Some(StepKind::Temper));
+        assert!(p.due(StepKind::Temper));
+        assert!(p.due(StepKind::Grind));
+        assert!(!p.due(StepKind::Assemble));
+
+        p.history.push(Step::Grind(Finish::Ground));
+        assert!(p.next_step() == Some(StepKind::Assemble));
+        p.history.push(Step::Assemble);
+        assert!(p.is_finished());
+    }
+
+    #[test]
+    fn reputation_from_sales() {
+        assert_eq!(Product::new(Material::Iron).reputation(), 0);
+        assert_eq!(finished_spear().reputation(), 1);
+
+        let mut half_done = finished_spear();
+        half_done.history.pop();
+        assert_eq!(half_done.reputation(), -1);
+
+        let mut masterpiece = finished_spear();
+        masterpiece.finish = Finish::Mirror;
+        masterpiece.hardness = 90;
+        assert_eq!(masterpiece.reputation(), 3);
+    }
+
+    #[test]
+    fn orders_check_the_item() {
+        let order = Order {
+            form: Form::Spear,
+            material: Material::Iron,
+            finish: Finish::Ground,
+            part: None,
+            reward: 100,
+            due: 3,
+        };
+        let mut p = finished_spear();
+        p.finish = Finish::Polished;
+        assert!(order.refusal(&p).is_none());
+
+        p.finish = Finish::Rough;
+        assert!(order.refusal(&p).is_some());
+
+        p.finish = Finish::Ground;
+        p.material = Material::Steel;
+        assert!(order.refusal(&p).is_some());
+
+        let mut half_done = finished_spear();
+        half_done.finish = Finish::Ground;
+        half_done.history.pop();
+        assert!(order.refusal(&half_done).is_some());
+    }
+
+    #[test]
+    fn orders_check_the_part() {
+        let order = Order {
+            form: Form::Spear,
+            material: Material::Iron,
+            finish: Finish::Rough,
+            part: Some(Part::LeatherGrip),
+            reward: 100,
+            due: 3,
+        };
+        let mut p = finished_spear();
+        assert!(order.refusal(&p).is_some());
+
+        p.parts.push(Part::LeatherGrip);
+        assert!(order.refusal(&p).is_none());
+    }
+
+    #[test]
+    fn missed_orders_cost_reputation() {
+        let mut state = GameState::new();
+        state.take_order();
+        state.orders[0].due = state.day;
+        state.take_order();
+        state.orders[1].due = state.day + 1;
+        assert_eq!(state.expire_orders().len(), 1);
+        assert_eq!(state.orders.len(), 1);
+        assert_eq!(state.reputation, -ORDER_REPUTATION);
+    }
+
+    #[test]
+    fn smelts_by_the_mix() {
+        let mix = |ores: &[(Ore, usize)]| {
+            let mut charge = vec![];
+            for (o, n) in ores {
+                charge.extend(std::iter::repeat_n(*o, *n));
+            }
+            smelt(&charge)
+        };
+
+        // Right on the target ratio comes out pure
+        let (m, purity) = mix(&[(Ore::Copper, 7), (Ore::Tin, 1)]).unwrap();
+        assert!(m == Material::Bronze);
+        assert_eq!(purity, 100);
+
+        let (m, purity) = mix(&[(Ore::Iron, 7), (Ore::Charcoal, 1)]).unwrap();
+        assert!(m == Material::Iron);
+        assert_eq!(purity, 100);
+
+        let (m, purity) = mix(&[(Ore::Iron, 3), (Ore::Charcoal, 1)]).unwrap();
+        assert!(m == Material::Steel);
+        assert_eq!(purity, 100);
+
+        // No charcoal is 12 points off, stray tin costs its share
+        let (m, purity) = mix(&[(Ore::Iron, 4)]).unwrap();
+        assert!(m == Material::Iron);
+        assert_eq!(purity, 52);
+
+        let (_, purity) = mix(&[(Ore::Iron, 7), (Ore::Charcoal, 1), (Ore::Tin, 8)]).unwrap();
+        assert_eq!(purity, 0);
+    }
+
+    #[test]
+    fn smelt_rejects_bad_charges() {
+        assert!(smelt(&[]).is_err());
+        assert!(smelt(&[Ore::Copper, Ore::Iron, Ore::Tin]).is_err());
+        assert!(smelt(&[Ore::Copper, Ore::Copper]).is_err());
+        assert!(smelt(&[Ore::Tin, Ore::Charcoal]).is_err());
+    }
+
+    #[test]
+    fn income_only_counts_this_run() {
+        let mut state = GameState::new();
+        state.transact(TransactionKind::Sale, String::from("Old"), 100);
+        state.run = 1;
+        state.transact(TransactionKind::Sale, String::from("New"), 40);
+        state.transact(TransactionKind::Loan, String::from("Loan"), 100);
+        assert_eq!(state.income(0), 40);
+    }
+}
@@ -0,0 +1,134 @@
+use super::anvil::*;
+use super::calendar::*;
+use super::game::*;
+use super::toast::*;
+use super::ui::*;
+use crate::handle_events;
+use crate::Controls;
+
+use sdl2::image::LoadTexture;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+
+use rand::Rng;
+
+use std::path::Path;
+use std::time::Duration;
+
+// Number of strokes in one session at the wheel
+const STROKES: i32 = 6;
+
+// Lanes line up with the anvil's
+const LANE_X: [i32; 4] = [24, 168, 312, 456];
+
+// Finish earned from the share of good contact with the wheel
+fn finish_for(score: i32) -> Finish {
+    if score >= 90 {
+        Finish::Mirror
+    } else if score >= 70 {
+        Finish::Polished
+    } else if score >= 40 {
+        Finish::Ground
+    } else {
+        Finish::Rough
+    }
+}
+
+// Hold a lane while its stroke passes over the receptacle, let go after
+pub fn run_grind(game: &mut Game, index: usize) {
+    let texture_creator = game.canvas.texture_creator();
+
+    let bang = texture_creator
+        .load_texture(Path::new("assets/Bang.png"))
+        .unwrap();
+
+    let receptacles: Vec<Rect> = LANE_X.iter().map(|x| Rect::new(*x, 390, 120, 60)).collect();
+
+    let mut rng = rand::thread_rng();
+    let mut cs = Controls::new();
+
+    // Strokes, with the lane each one is in
+    let mut strokes = Vec::<(usize, Bangs)>::new();
+    let step = game.settings.note_step();
+    let mut spawned = 0;
+    let mut countdown = 0;
+
+    // Frames spent on the wheel, and frames spent doing it right
+    let mut contact = 0;
+    let mut good = 0;
+
+    loop {
+        // Handle events
+        if !handle_events(&mut cs, &mut game.input) {
+            return;
+        }
+
+        // Spawn strokes of random length
+        if countdown > 0 {
+            countdown -= 1;
+        } else if spawned < STROKES {
+            let lane: usize = rng.gen_range(0..4);
+            let len: u32 = rng.gen_range(60..180);
+            strokes.push((
+                lane,
+                Bangs {
+                    image: lane_texture(&texture_creator, lane),
+                    rect: Rect::new(LANE_X[lane], -(len as i32), 120, len),
+                },
+            ));
+            spawned += 1;
+            countdown = 30 + len as i32 / 5;
+        }
+
+        // Move strokes, dropping the ones that are done
+        strokes.retain_mut(|(_, s)| !update_note(s, step));
+        if spawned >= STROKES && strokes.is_empty() {
+            break;
+        }
+
+        // Score each lane
+        for (lane, r) in receptacles.iter().enumerate() {
+            let over = strokes
+                .iter()
+                .any(|(l, s)| *l == lane && s.rect.has_intersection(*r));
+
+            if over {
+                contact += 1;
+                if cs.lanes[lane] {
+                    good += 1;
+                }
+            } else if cs.lanes[lane] {
+                // Grinding nothing scuffs the surface
+                contact += 1;
+            }
+        }
+
+        // Draw
+        game.canvas.set_draw_color(Color::RGB(255, 255, 255));
+        game.canvas.clear();
+
+        for (_, s) in &strokes {
+            game.canvas.copy(&s.image, None, s.rect).unwrap();
+        }
+        for r in &receptacles {
+            game.canvas.copy(&bang, None, *r).unwrap();
+        }
+
+        game.toasts.present(&mut game.canvas);
+
+        // Sleep
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+
+    let score = if contact > 0 { 100 * good / contact } else { 0 };
+    let finish = finish_for(score);
+
+    let p = &mut game.state.inventory[index];
+    p.finish = finish;
+    p.history.push(Step::Grind(finish));
+    p.appraise();
+
+    let message = format!("Finish: {}", p.finish);
+    game.state.spend_time(Activity::Grind);
+    notify(game, &message, Severity::Info);
+}
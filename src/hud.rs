@@ -0,0 +1,277 @@
+use super::calendar::*;
+use super::game::*;
+use super::input::*;
+use super::stations::*;
+use super::text::*;
+use super::ui::*;
+use super::widgets::*;
+use super::workshop::*;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::BlendMode;
+use sdl2::ttf::Font;
+
+const ROW_HEIGHT: i32 = 22;
+
+// Width of prompts and tooltips
+const BOX_WIDTH: u32 = 240;
+
+// How an item in the forge is coming along
+fn temp_word(t: &Temp) -> &'static str {
+    match t {
+        Temp::Under => "Heating",
+        Temp::Perfect => "Ready",
+        Temp::Over => "Too hot",
+    }
+}
+
+// Money, reputation and the time along the top, orders in the top right,
+// forge slots in the bottom left. Drawn over the workshop every frame
+pub fn draw_hud(game: &mut Game, font: &mut Font) {
+    let state = &game.state;
+    let grey = Color::RGB(160, 160, 160);
+    let red = Color::RGB(235, 52, 52);
+
+    let canvas = &mut game.canvas;
+    canvas.set_blend_mode(BlendMode::Blend);
+
+    // Top bar
+    let mut bar = Panel::new(Rect::new(0, 0, SCREEN_WIDTH, 28));
+    bar.color = Color::RGBA(0, 0, 0, 200);
+    bar.draw(canvas);
+
+    draw_string(
+        canvas,
+        font,
+        &clock_text(state),
+        TEXT,
+        Rect::new(8, 0, 340, 28),
+        &TextStyle::single_line(Align::Left),
+    );
+
+    let mut spans = vec![Span::new(
+        &format!("{}$", state.money),
+        if state.money < 0 { red } else { TEXT },
+    )];
+    if state.debt > 0 {
+        spans.push(Span::new(&format!("  Owed {}$", state.debt), red));
+    }
+    spans.push(Span::new(&format!("  Rep {}", state.reputation), TEXT));
+    draw_spans(
+        canvas,
+        font,
+        &spans,
+        Rect::new(340, 0, SCREEN_WIDTH - 348, 28),
+        &TextStyle::single_line(Align::Right),
+    );
+
+    // Orders, the ones due today in red
+    if !state.orders.is_empty() {
+        let h = ROW_HEIGHT * (state.orders.len() as i32 + 1) + 8;
+        let mut panel = Panel::new(Rect::new(SCREEN_WIDTH as i32 - 388, 36, 380, h as u32));
+        panel.color = Color::RGBA(0, 0, 0, 200);
+        panel.draw(canvas);
+
+        let row = |i: i32| {
+            Rect::new(
+                panel.rect.x + 8,
+                panel.rect.y + 4 + ROW_HEIGHT * i,
+                panel.rect.width() - 16,
+                ROW_HEIGHT as u32,
+            )
+        };
+        draw_string(
+            canvas,
+            font,
+            "Orders",
+            TEXT,
+            row(0),
+            &TextStyle::single_line(Align::Left),
+        );
+        for (i, order) in state.orders.iter().enumerate() {
+            let color = if order.due <= state.day { red } else { TEXT };
+            let spans = vec![
+                Span::new(&order.to_string(), color),
+                Span::new(&format!("  {}$", order.reward), grey),
+            ];
+            draw_spans(
+                canvas,
+                font,
+                &spans,
+                row(i as i32 + 1),
+                &TextStyle::single_line(Align::Left),
+            );
+        }
+    }
+
+    // Forge, one row per slot
+    let forge: Vec<&Product> = state
+        .inventory
+        .iter()
+        .filter(|x| x.location == Location::Forge)
+        .collect();
+    let slots = state.upgrades.forge_space;
+
+    let h = ROW_HEIGHT * (slots + 1) + 8;
+    let mut panel = Panel::new(Rect::new(8, SCREEN_HEIGHT as i32 - h - 8, 260, h as u32));
+    panel.color = Color::RGBA(0, 0, 0, 200);
+    panel.draw(canvas);
+
+    let row = |i: i32| {
+        Rect::new(
+            panel.rect.x + 8,
+            panel.rect.y + 4 + ROW_HEIGHT * i,
+            panel.rect.width() - 16,
+            ROW_HEIGHT as u32,
+        )
+    };
+    draw_string(
+        canvas,
+        font,
+        &format!("Forge {}/{}", forge.len(), slots),
+        TEXT,
+        row(0),
+        &TextStyle::single_line(Align::Left),
+    );
+
+    for i in 0..slots {
+        let spans = match forge.get(i as usize) {
+            Some(p) => {
+                let temp = p.temp_val();
+                vec![
+                    Span::new(&p.material.to_string(), material_color(p.material)),
+                    Span::new(&format!(" {}  ", p.form), TEXT),
+                    Span::new(
+                        &format!("{}° {}", p.temp, temp_word(&temp)),
+                        temp_color(&temp),
+                    ),
+                ]
+            }
+            None => vec![Span::new("Empty", grey)],
+        };
+        draw_spans(
+            canvas,
+            font,
+            &spans,
+            row(i + 1),
+            &TextStyle::single_line(Align::Left),
+        );
+    }
+
+    canvas.set_blend_mode(BlendMode::None);
+}
+
+// Name of the button that uses stations, for whatever's being played with
+fn interact_key(game: &Game) -> String {
+    let bindings = &game.input.bindings;
+    let button = bindings.buttons_for(Action::Interact).first();
+    let key = bindings.keys_for(Action::Interact).first();
+    match (button, key) {
+        (Some(b), _) if !game.input.controllers.is_empty() => b.string(),
+        (_, Some(k)) => k.name(),
+        _ => String::from("Enter"),
+    }
+}
+
+// Blocks of text in a dark box centered on x, above or below y. It's kept
+// on screen and clear of the top bar
+fn draw_box(game: &mut Game, font: &mut Font, blocks: &[Vec<Span>], x: i32, y: i32, above: bool) {
+    let style = TextStyle::new();
+    let inner = BOX_WIDTH as i32 - 16;
+    let measure = |s: &str| text_width(font, s);
+    let heights: Vec<i32> = blocks
+        .iter()
+        .map(|b| {
+            let lines = layout(
+                &measure,
+                font.height(),
+                b,
+                inner,
+                SCREEN_HEIGHT as i32,
+                &style,
+            );
+            let lines = lines.len() as i32;
+            lines * (font.height() + style.line_spacing)
+        })
+        .collect();
+    let h = heights.iter().sum::<i32>() + 8;
+
+    let top = if above { y - h - 4 } else { y };
+    let top = top.clamp(32, SCREEN_HEIGHT as i32 - h - 4);
+    let left = (x - BOX_WIDTH as i32 / 2).clamp(4, (SCREEN_WIDTH - BOX_WIDTH) as i32 - 4);
+
+    let canvas = &mut game.canvas;
+    canvas.set_blend_mode(BlendMode::Blend);
+    let mut panel = Panel::new(Rect::new(left, top, BOX_WIDTH, h as u32));
+    panel.color = Color::RGBA(0, 0, 0, 220);
+    panel.border = true;
+    panel.draw(canvas);
+    canvas.set_blend_mode(BlendMode::None);
+
+    let mut y = top + 6;
+    for (b, bh) in blocks.iter().zip(heights) {
+        draw_spans(
+            canvas,
+            font,
+            b,
+            Rect::new(left + 8, y, inner as u32, bh as u32),
+            &style,
+        );
+        y += bh;
+    }
+}
+
+// Line saying why a station can't be used, or what to bear in mind
+fn state_line(state: &StationState) -> Option<Vec<Span>> {
+    match state {
+        StationState::Ready => None,
+        StationState::Note(s) => Some(vec![Span::new(s, Color::RGB(235, 204, 52))]),
+        StationState::Disabled(s) => Some(vec![Span::new(s, Color::RGB(235, 52, 52))]),
+    }
+}
+
+// "Press Return to use Forge" over the station the player is standing at
+pub fn draw_prompt(game: &mut Game, font: &mut Font, station: &Station) {
+    let state = station_state(game, station.kind);
+    let mut blocks = Vec::new();
+    if let StationState::Disabled(_) = state {
+        blocks.push(vec![Span::new(&station.kind.to_string(), TEXT)]);
+    } else {
+        blocks.push(vec![
+            Span::new("Press ", TEXT),
+            Span::new(&interact_key(game), Color::RGB(119, 235, 52)),
+            Span::new(&format!(" to use {}", station.kind), TEXT),
+        ]);
+    }
+    if let Some(line) = state_line(&state) {
+        blocks.push(line);
+    }
+
+    let c = station.sprite.center();
+    draw_box(game, font, &blocks, c.x(), station.sprite.top(), true);
+}
+
+// What a station does, next to the mouse pointer
+pub fn draw_tooltip(game: &mut Game, font: &mut Font, station: &Station, mouse: (i32, i32)) {
+    let state = station_state(game, station.kind);
+    let mut blocks = vec![
+        vec![Span::new(&station.kind.to_string(), TEXT)],
+        vec![Span::new(
+            &station.kind.description(),
+            Color::RGB(200, 200, 200),
+        )],
+    ];
+    if let Some(line) = state_line(&state) {
+        blocks.push(line);
+    }
+
+    draw_box(
+        game,
+        font,
+        &blocks,
+        mouse.0 + BOX_WIDTH as i32 / 2 + 12,
+        mouse.1 + 16,
+        false,
+    );
+}
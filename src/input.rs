@@ -0,0 +1,677 @@
+use super::game::*;
+use super::ui::*;
+
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Texture;
+use sdl2::EventPump;
+use sdl2::GameControllerSubsystem;
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Everything the player can ask the game to do
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Interact,
+    Back,
+    SwitchTab,
+    Lane1,
+    Lane2,
+    Lane3,
+    Lane4,
+}
+
+impl Action {
+    pub fn all() -> Vec<Action> {
+        vec![
+            Action::MoveUp,
+            Action::MoveDown,
+            Action::MoveLeft,
+            Action::MoveRight,
+            Action::Interact,
+            Action::Back,
+            Action::SwitchTab,
+            Action::Lane1,
+            Action::Lane2,
+            Action::Lane3,
+            Action::Lane4,
+        ]
+    }
+    // Name used in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Interact => "interact",
+            Action::Back => "back",
+            Action::SwitchTab => "switch_tab",
+            Action::Lane1 => "lane1",
+            Action::Lane2 => "lane2",
+            Action::Lane3 => "lane3",
+            Action::Lane4 => "lane4",
+        }
+    }
+    pub fn from_name(s: &str) -> Option<Action> {
+        Action::all().into_iter().find(|a| a.name() == s)
+    }
+    // Anvil and grindstone lane, left to right
+    pub fn lane(&self) -> Option<usize> {
+        match self {
+            Action::Lane1 => Some(0),
+            Action::Lane2 => Some(1),
+            Action::Lane3 => Some(2),
+            Action::Lane4 => Some(3),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Interact => "Interact",
+            Action::Back => "Back",
+            Action::SwitchTab => "Switch tab",
+            Action::Lane1 => "Lane 1",
+            Action::Lane2 => "Lane 2",
+            Action::Lane3 => "Lane 3",
+            Action::Lane4 => "Lane 4",
+        })
+    }
+}
+
+// How far a stick has to be pushed before it counts
+const STICK_DEADZONE: i16 = 12000;
+
+// Keys and controller buttons for each action, a key can do different
+// things on different screens
+pub struct Bindings {
+    pub keys: HashMap<Action, Vec<Keycode>>,
+    pub buttons: HashMap<Action, Vec<Button>>,
+}
+
+// A key or button waiting to be bound
+pub enum Binding {
+    Key(Keycode),
+    Button(Button),
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        let mut keys = HashMap::new();
+        keys.insert(Action::MoveUp, vec![Keycode::Up, Keycode::W]);
+        keys.insert(Action::MoveDown, vec![Keycode::Down, Keycode::S]);
+        keys.insert(Action::MoveLeft, vec![Keycode::Left, Keycode::A]);
+        keys.insert(Action::MoveRight, vec![Keycode::Right, Keycode::D]);
+        keys.insert(Action::Interact, vec![Keycode::Return, Keycode::Space]);
+        keys.insert(Action::Back, vec![Keycode::Escape]);
+        keys.insert(Action::SwitchTab, vec![Keycode::Tab]);
+        keys.insert(Action::Lane1, vec![Keycode::D, Keycode::Left]);
+        keys.insert(Action::Lane2, vec![Keycode::F, Keycode::Up]);
+        keys.insert(Action::Lane3, vec![Keycode::J, Keycode::Down]);
+        keys.insert(Action::Lane4, vec![Keycode::K, Keycode::Right]);
+
+        // The d-pad moves, A and B accept and go back. The lanes share the
+        // face buttons, each on the side its arrow key points: X left, Y up,
+        // A down, B right
+        let mut buttons = HashMap::new();
+        buttons.insert(Action::MoveUp, vec![Button::DPadUp]);
+        buttons.insert(Action::MoveDown, vec![Button::DPadDown]);
+        buttons.insert(Action::MoveLeft, vec![Button::DPadLeft]);
+        buttons.insert(Action::MoveRight, vec![Button::DPadRight]);
+        buttons.insert(Action::Interact, vec![Button::A]);
+        buttons.insert(Action::Back, vec![Button::B]);
+        buttons.insert(Action::SwitchTab, vec![Button::RightShoulder]);
+        buttons.insert(Action::Lane1, vec![Button::X]);
+        buttons.insert(Action::Lane2, vec![Button::Y]);
+        buttons.insert(Action::Lane3, vec![Button::A]);
+        buttons.insert(Action::Lane4, vec![Button::B]);
+
+        Bindings { keys, buttons }
+    }
+}
+
+impl Bindings {
+    // Every action the key is bound to
+    pub fn actions(&self, key: Keycode) -> Vec<Action> {
+        Action::all()
+            .into_iter()
+            .filter(|a| self.keys_for(*a).contains(&key))
+            .collect()
+    }
+
+    // Every action the button is bound to
+    pub fn button_actions(&self, button: Button) -> Vec<Action> {
+        Action::all()
+            .into_iter()
+            .filter(|a| self.buttons_for(*a).contains(&button))
+            .collect()
+    }
+
+    pub fn keys_for(&self, action: Action) -> &[Keycode] {
+        match self.keys.get(&action) {
+            Some(k) => k,
+            None => &[],
+        }
+    }
+
+    pub fn buttons_for(&self, action: Action) -> &[Button] {
+        match self.buttons.get(&action) {
+            Some(b) => b,
+            None => &[],
+        }
+    }
+
+    // "Up, W / dpup"
+    pub fn describe(&self, action: Action) -> String {
+        let mut names: Vec<String> = self.keys_for(action).iter().map(|k| k.name()).collect();
+        if names.is_empty() {
+            names.push(String::from("-"));
+        }
+
+        let buttons: Vec<String> = self
+            .buttons_for(action)
+            .iter()
+            .map(|b| b.string())
+            .collect();
+        if buttons.is_empty() {
+            names.join(", ")
+        } else {
+            format!("{} / {}", names.join(", "), buttons.join(", "))
+        }
+    }
+
+    // Lines of "action = Key, Key" and "pad.action = button, button",
+    // anything missing keeps its default
+    pub fn parse(text: &str) -> Result<Bindings, String> {
+        let mut b = Bindings::default();
+
+        for (i, raw) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = raw.trim();
+
+            // Skip blanks + comments
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, keys) = line
+                .split_once('=')
+                .ok_or(format!("line {}: expected action = keys", line_no))?;
+            let name = name.trim();
+
+            // Controller buttons
+            if let Some(name) = name.strip_prefix("pad.") {
+                let action = Action::from_name(name)
+                    .ok_or(format!("line {}: unknown action '{}'", line_no, name))?;
+
+                let mut buttons = Vec::new();
+                for b in keys.split(',').map(|b| b.trim()).filter(|b| !b.is_empty()) {
+                    let button = Button::from_string(b)
+                        .ok_or(format!("line {}: unknown button '{}'", line_no, b))?;
+                    buttons.push(button);
+                }
+                b.buttons.insert(action, buttons);
+                continue;
+            }
+
+            let action = Action::from_name(name)
+                .ok_or(format!("line {}: unknown action '{}'", line_no, name))?;
+
+            let mut codes = Vec::new();
+            for k in keys.split(',').map(|k| k.trim()).filter(|k| !k.is_empty()) {
+                let code = Keycode::from_name(k)
+                    .ok_or(format!("line {}: unknown key '{}'", line_no, k))?;
+                codes.push(code);
+            }
+            b.keys.insert(action, codes);
+        }
+
+        Ok(b)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut s = String::from("# Key bindings, one action per line\n");
+        for a in Action::all() {
+            let names: Vec<String> = self.keys_for(a).iter().map(|k| k.name()).collect();
+            s.push_str(&format!("{} = {}\n", a.name(), names.join(", ")));
+        }
+        for a in Action::all() {
+            let names: Vec<String> = self.buttons_for(a).iter().map(|b| b.string()).collect();
+            s.push_str(&format!("pad.{} = {}\n", a.name(), names.join(", ")));
+        }
+        s
+    }
+
+    // Saved bindings, or the defaults if there are none yet. A file that
+    // can't be read is an error, so the player can be told
+    pub fn load() -> Result<Bindings, String> {
+        match fs::read_to_string(config_dir().join("controls.txt")) {
+            Ok(text) => Bindings::parse(&text).map_err(|e| format!("controls.txt {}", e)),
+            Err(_) => Ok(Bindings::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let dir = config_dir();
+        fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+        let path = dir.join("controls.txt");
+        fs::write(&path, self.to_text()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+// Where the game keeps its settings for this user
+pub fn config_dir() -> PathBuf {
+    let base = if let Ok(dir) = env::var("XDG_CONFIG_HOME") {
+        PathBuf::from(dir)
+    } else if let Ok(dir) = env::var("APPDATA") {
+        PathBuf::from(dir)
+    } else if let Ok(home) = env::var("HOME") {
+        PathBuf::from(home).join(".config")
+    } else {
+        PathBuf::from(".")
+    };
+    base.join("smithy")
+}
+
+// What a frame's worth of events came to
+pub enum InputEvent {
+    Quit,
+    Press(Action),
+    Release(Action),
+    // Mouse position in window coordinates
+    MouseMove(i32, i32),
+    Click(i32, i32),
+    // Positive when the wheel is rolled up
+    Wheel(i32),
+}
+
+// Turns raw SDL events into actions
+pub struct Input {
+    pub event_pump: EventPump,
+    pub bindings: Bindings,
+    pub controller_subsystem: GameControllerSubsystem,
+    // Controllers plugged in, opened as SDL reports them
+    pub controllers: Vec<GameController>,
+    // Which way the left stick is pushed, -1, 0 or 1 on each axis
+    stick: (i32, i32),
+    // Mouse events are dropped when this is off
+    pub mouse: bool,
+}
+
+// Which way a stick axis is pushed
+fn stick_direction(value: i16) -> i32 {
+    if value < -STICK_DEADZONE {
+        -1
+    } else if value > STICK_DEADZONE {
+        1
+    } else {
+        0
+    }
+}
+
+impl Input {
+    pub fn new(
+        event_pump: EventPump,
+        controller_subsystem: GameControllerSubsystem,
+        bindings: Bindings,
+    ) -> Input {
+        Input {
+            event_pump,
+            bindings,
+            controller_subsystem,
+            controllers: Vec::new(),
+            stick: (0, 0),
+            mouse: true,
+        }
+    }
+
+    // Controllers already plugged in at startup are reported as added too
+    fn add_controller(&mut self, index: u32) {
+        if !self.controller_subsystem.is_game_controller(index) {
+            return;
+        }
+        // One that won't open is left out, the keyboard still works
+        if let Ok(c) = self.controller_subsystem.open(index) {
+            self.controllers.push(c);
+        }
+    }
+
+    fn remove_controller(&mut self, id: u32) {
+        self.controllers.retain(|c| c.instance_id() != id);
+    }
+
+    pub fn poll(&mut self) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        for e in self.event_pump.poll_iter() {
+            let from_mouse = matches!(
+                e,
+                Event::MouseMotion { .. }
+                    | Event::MouseButtonDown { .. }
+                    | Event::MouseButtonUp { .. }
+                    | Event::MouseWheel { .. }
+            );
+            if from_mouse && !self.mouse {
+                continue;
+            }
+
+            match e {
+                Event::Quit { .. } => events.push(InputEvent::Quit),
+                Event::MouseMotion { x, y, .. } => events.push(InputEvent::MouseMove(x, y)),
+                Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Left,
+                    x,
+                    y,
+                    ..
+                } => events.push(InputEvent::Click(x, y)),
+                // Right click backs out of things like Escape does
+                Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Right,
+                    ..
+                } => events.push(InputEvent::Press(Action::Back)),
+                Event::MouseButtonUp {
+                    mouse_btn: MouseButton::Right,
+                    ..
+                } => events.push(InputEvent::Release(Action::Back)),
+                Event::MouseWheel { y, .. } => events.push(InputEvent::Wheel(y)),
+                Event::ControllerDeviceAdded { which, .. } => added.push(which),
+                Event::ControllerDeviceRemoved { which, .. } => removed.push(which),
+                Event::ControllerButtonDown { button, .. } => {
+                    for a in self.bindings.button_actions(button) {
+                        events.push(InputEvent::Press(a));
+                    }
+                }
+                Event::ControllerButtonUp { button, .. } => {
+                    for a in self.bindings.button_actions(button) {
+                        events.push(InputEvent::Release(a));
+                    }
+                }
+                Event::ControllerAxisMotion {
+                    axis: Axis::LeftX,
+                    value,
+                    ..
+                } => {
+                    let dir = stick_direction(value);
+                    if dir != self.stick.0 {
+                        stick_events(
+                            &mut events,
+                            self.stick.0,
+                            dir,
+                            Action::MoveLeft,
+                            Action::MoveRight,
+                        );
+                        self.stick.0 = dir;
+                    }
+                }
+                Event::ControllerAxisMotion {
+                    axis: Axis::LeftY,
+                    value,
+                    ..
+                } => {
+                    let dir = stick_direction(value);
+                    if dir != self.stick.1 {
+                        stick_events(
+                            &mut events,
+                            self.stick.1,
+                            dir,
+                            Action::MoveUp,
+                            Action::MoveDown,
+                        );
+                        self.stick.1 = dir;
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(k), ..
+                } => {
+                    for a in self.bindings.actions(k) {
+                        events.push(InputEvent::Press(a));
+                    }
+                }
+                Event::KeyUp {
+                    keycode: Some(k), ..
+                } => {
+                    for a in self.bindings.actions(k) {
+                        events.push(InputEvent::Release(a));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        for index in added {
+            self.add_controller(index);
+        }
+        for id in removed {
+            self.remove_controller(id);
+        }
+        events
+    }
+
+    // Next key or button pressed, for rebinding. None if the window is closed
+    pub fn wait_binding(&mut self) -> Option<Binding> {
+        loop {
+            for e in self.event_pump.poll_iter() {
+                match e {
+                    Event::Quit { .. } => return None,
+                    Event::KeyDown {
+                        keycode: Some(k), ..
+                    } => return Some(Binding::Key(k)),
+                    Event::ControllerButtonDown { button, .. } => {
+                        return Some(Binding::Button(button))
+                    }
+                    _ => (),
+                }
+            }
+            ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+        }
+    }
+}
+
+// Let go of the old stick direction and press the new one
+fn stick_events(events: &mut Vec<InputEvent>, old: i32, new: i32, neg: Action, pos: Action) {
+    match old {
+        -1 => events.push(InputEvent::Release(neg)),
+        1 => events.push(InputEvent::Release(pos)),
+        _ => (),
+    }
+    match new {
+        -1 => events.push(InputEvent::Press(neg)),
+        1 => events.push(InputEvent::Press(pos)),
+        _ => (),
+    }
+}
+
+// List every action and its keys and buttons, Enter to rebind one
+pub fn controls_screen(game: &mut Game) {
+    let actions = Action::all();
+
+    // One row per action plus resetting everything
+    let rows: Vec<Rect> = (0..actions.len() as i32 + 1)
+        .map(|i| Rect::new(20, 20 + 36 * i, 560, 36))
+        .collect();
+    let mut active: i32 = 0;
+
+    // Load font
+    let mut font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 20)
+        .unwrap();
+
+    loop {
+        match handle_selection(&mut game.input, &mut active, &rows) {
+            HandlerRet::Exit => return,
+            HandlerRet::Accept => {
+                if active >= 0 && active < actions.len() as i32 {
+                    rebind(game, actions[active as usize]);
+                } else if active == actions.len() as i32 {
+                    game.input.bindings = Bindings::default();
+                    if game.input.bindings.save().is_err() {
+                        display_error(game, "Couldn't save controls");
+                    }
+                }
+            }
+            _ => (),
+        };
+
+        // Create tc
+        let tc = game.canvas.texture_creator();
+
+        let mut labels: Vec<String> = actions
+            .iter()
+            .map(|a| format!("{}: {}", a, game.input.bindings.describe(*a)))
+            .collect();
+        labels.push(String::from("Reset to defaults"));
+
+        // Draw
+        game.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        game.canvas.clear();
+
+        if active >= 0 {
+            game.canvas.set_draw_color(Color::RGB(50, 50, 50));
+            game.canvas.fill_rect(rows[active as usize]).unwrap();
+        }
+
+        let mut texts = Vec::<Texture>::new();
+        for label in &labels {
+            texts.push(create_text(
+                label,
+                &tc,
+                &mut font,
+                Color::RGB(255, 255, 255),
+            ));
+        }
+        for (i, t) in texts.iter().enumerate() {
+            let r = Rect::new(
+                40,
+                20 + 36 * i as i32 + 6,
+                t.query().width,
+                t.query().height,
+            );
+            game.canvas.copy(t, None, Some(r)).unwrap();
+        }
+
+        game.toasts.present(&mut game.canvas);
+
+        // Sleep
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+// Wait for a key or button and make it the only one of its kind for the action
+fn rebind(game: &mut Game, action: Action) {
+    {
+        // Load font
+        let mut font = game
+            .ttf
+            .load_font("assets/SupermercadoOne-Regular.ttf", 24)
+            .unwrap();
+
+        // Create tc
+        let tc = game.canvas.texture_creator();
+
+        let s = format!("Press a key or button for {}", action);
+        let text = create_text(&s, &tc, &mut font, Color::RGB(255, 255, 255));
+        let backdrop = Rect::new(50, 180, 500, 120);
+        let r = center_text(Rect::new(50, 180, 500, 80), &text);
+
+        game.canvas.set_draw_color(Color::RGB(50, 50, 50));
+        game.canvas.fill_rect(backdrop).unwrap();
+        game.canvas.copy(&text, None, Some(r)).unwrap();
+
+        // How to back out, there's nothing to back out with when binding Back
+        if action != Action::Back {
+            let cancel = match game.input.bindings.buttons_for(Action::Back).first() {
+                Some(b) => format!("Escape or {} to cancel", b.string()),
+                None => String::from("Escape to cancel"),
+            };
+            let hint = create_text(&cancel, &tc, &mut font, Color::RGB(160, 160, 160));
+            let r = center_text(Rect::new(50, 250, 500, 40), &hint);
+            game.canvas.copy(&hint, None, Some(r)).unwrap();
+        }
+        game.toasts.present(&mut game.canvas);
+    }
+
+    match game.input.wait_binding() {
+        // Escape or a Back button backs out, unless it's Back being bound
+        Some(Binding::Key(Keycode::Escape)) if action != Action::Back => return,
+        Some(Binding::Button(b))
+            if action != Action::Back
+                && game.input.bindings.buttons_for(Action::Back).contains(&b) =>
+        {
+            return
+        }
+        Some(Binding::Key(k)) => {
+            game.input.bindings.keys.insert(action, vec![k]);
+        }
+        Some(Binding::Button(b)) => {
+            game.input.bindings.buttons.insert(action, vec![b]);
+        }
+        None => return,
+    }
+
+    if game.input.bindings.save().is_err() {
+        display_error(game, "Couldn't save controls");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_text() {
+        let mut b = Bindings::default();
+        b.keys.insert(Action::Interact, vec![Keycode::E]);
+        b.keys.insert(Action::Back, vec![]);
+        b.buttons
+            .insert(Action::SwitchTab, vec![Button::Back, Button::Start]);
+
+        let text = b.to_text();
+        assert_eq!(Bindings::parse(&text).unwrap().to_text(), text);
+    }
+
+    #[test]
+    fn missing_actions_keep_defaults() {
+        let b = Bindings::parse("# Just one\ninteract = E\npad.back = x\n").unwrap();
+        assert_eq!(b.keys_for(Action::Interact), &[Keycode::E]);
+        assert_eq!(b.buttons_for(Action::Back), &[Button::X]);
+        assert_eq!(b.keys_for(Action::MoveUp), &[Keycode::Up, Keycode::W]);
+        assert_eq!(b.buttons_for(Action::Interact), &[Button::A]);
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        let bad = [
+            "interact E\n",
+            "jump = Space\n",
+            "interact = NotAKey\n",
+            "pad.jump = a\n",
+            "pad.interact = z\n",
+        ];
+        for text in bad {
+            assert!(Bindings::parse(text).is_err(), "{:?}", text);
+        }
+    }
+}
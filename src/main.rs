@@ -1,21 +1,40 @@
 extern crate sdl2;
 
 mod anvil;
+mod calendar;
 mod game;
+mod grind;
+mod hud;
+mod input;
+mod quench;
+mod report;
+mod scenario;
+mod settings;
+mod shop;
+mod stations;
+mod text;
+mod toast;
 mod ui;
+mod widgets;
+mod workshop;
 
-use anvil::*;
+use calendar::*;
 use game::*;
+use hud::*;
+use input::*;
+use report::*;
+use scenario::*;
+use stations::*;
+use toast::*;
 use ui::*;
+use workshop::*;
 
-use sdl2::event::Event;
 use sdl2::image::LoadTexture;
-use sdl2::keyboard::Keycode;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::Texture;
-use sdl2::EventPump;
 
+use std::collections::HashMap;
 use std::path::Path;
 use std::time::Duration;
 
@@ -25,6 +44,18 @@ pub struct Controls {
     pub left: bool,
     pub right: bool,
     pub enter: bool,
+    // Anvil and grindstone lanes, left to right
+    pub lanes: [bool; 4],
+    // Where the mouse was last clicked, until it's dealt with
+    pub click: Option<(i32, i32)>,
+    // Where the mouse is, for tooltips
+    pub mouse: Option<(i32, i32)>,
+}
+
+impl Default for Controls {
+    fn default() -> Controls {
+        Controls::new()
+    }
 }
 
 impl Controls {
@@ -35,146 +66,54 @@ impl Controls {
             left: false,
             right: false,
             enter: false,
+            lanes: [false; 4],
+            click: None,
+            mouse: None,
         }
     }
-}
-
-fn max(x: i32, y: i32) -> i32 {
-    if x > y {
-        return x;
-    } else {
-        return y;
-    }
-}
-
-fn min(x: i32, y: i32) -> i32 {
-    if y > x {
-        return x;
-    } else {
-        return y;
-    }
-}
-
-pub fn update_player_rect(c: &Controls, r: &mut Rect) {
-    if c.up {
-        r.y -= 7;
-    }
-    if c.down {
-        r.y += 7;
-    }
-    if c.left {
-        r.x -= 7;
-    }
-    if c.right {
-        r.x += 7;
+    // Hold or let go of whatever the action controls
+    fn set(&mut self, action: Action, held: bool) {
+        match action {
+            Action::MoveUp => self.up = held,
+            Action::MoveDown => self.down = held,
+            Action::MoveLeft => self.left = held,
+            Action::MoveRight => self.right = held,
+            Action::Interact => self.enter = held,
+            _ => {
+                if let Some(lane) = action.lane() {
+                    self.lanes[lane] = held;
+                }
+            }
+        }
     }
-
-    // Clamp
-    r.y = max(r.y, 60);
-    r.x = min(r.x, 560);
-    r.x = max(r.x, 0);
-    r.y = min(r.y, 440);
 }
 
-fn handle_events(controls: &mut Controls, event_pump: &mut EventPump) -> bool {
-    for event in event_pump.poll_iter() {
+fn handle_events(controls: &mut Controls, input: &mut Input) -> bool {
+    for event in input.poll() {
         match event {
-            Event::Quit { .. } => {
+            InputEvent::Quit => {
                 return false;
             }
-            Event::KeyDown {
-                keycode: Some(Keycode::Up),
-                ..
-            } => {
-                controls.up = true;
-            }
-            Event::KeyDown {
-                keycode: Some(Keycode::Down),
-                ..
-            } => {
-                controls.down = true;
-            }
-            Event::KeyDown {
-                keycode: Some(Keycode::Left),
-                ..
-            } => {
-                controls.left = true;
-            }
-            Event::KeyDown {
-                keycode: Some(Keycode::Right),
-                ..
-            } => {
-                controls.right = true;
-            }
-            Event::KeyDown {
-                keycode: Some(Keycode::Return),
-                ..
-            } => {
-                controls.enter = true;
-            }
-            Event::KeyUp {
-                keycode: Some(Keycode::Up),
-                ..
-            } => {
-                controls.up = false;
-            }
-            Event::KeyUp {
-                keycode: Some(Keycode::Down),
-                ..
-            } => {
-                controls.down = false;
-            }
-            Event::KeyUp {
-                keycode: Some(Keycode::Left),
-                ..
-            } => {
-                controls.left = false;
-            }
-            Event::KeyUp {
-                keycode: Some(Keycode::Right),
-                ..
-            } => {
-                controls.right = false;
-            }
-            Event::KeyUp {
-                keycode: Some(Keycode::Return),
-                ..
-            } => {
-                controls.enter = false;
-            }
-            _ => {}
+            InputEvent::Press(a) => controls.set(a, true),
+            InputEvent::Release(a) => controls.set(a, false),
+            InputEvent::Click(x, y) => controls.click = Some((x, y)),
+            InputEvent::MouseMove(x, y) => controls.mouse = Some((x, y)),
+            _ => (),
         }
     }
     true
 }
 
-pub fn move_to_furnace(game: &mut Game) {
-    let num_items = game
-        .state
-        .inventory
-        .iter()
-        .filter(|x| x.location == game::Location::Forge)
-        .count() as i32;
-
-    // Again probably add some error handling in the future
-    if num_items >= game.state.upgrades.forge_space {
-        display_error(game, "Not enough furnace space");
-        return;
-    }
-    // Pick what to move
-    let ret = display_inventory(game, Some(InventoryMode::Select));
-
-    // Move it
-    if let Some(i) = ret {
-        game.state.inventory[i].location = Location::Forge;
-    }
-}
+pub fn update_temp(game: &mut Game, frame: i32) {
+    // Bellows make the forge heat faster
+    let heat = 1 + game.state.upgrades.bellows_tier;
 
-pub fn update_temp(game: &mut Game) {
-    for mut item in game.state.inventory.iter_mut() {
-        match item.location {
-            Location::Forge => item.temp += 1,
-            _ => (),
+    for item in game.state.inventory.iter_mut() {
+        // Heavier items take longer, spread the fraction over frames
+        if item.location == Location::Forge {
+            let rate = heat * BAR_WEIGHT;
+            let w = item.weight.max(1);
+            item.temp += (frame + 1) * rate / w - frame * rate / w;
         }
     }
 }
@@ -184,86 +123,196 @@ pub fn main() {
 
     let mut game = Game::new();
 
+    // Pick the rules for this run
+    let scenarios = Scenario::load(Path::new("assets/scenarios.txt")).unwrap();
+    let scenario = match pick_scenario(&mut game, &scenarios) {
+        Some(i) => &scenarios[i],
+        None => return,
+    };
+    game.state.money = scenario.start_money;
+
+    // One customer is already waiting on the first day
+    game.state.take_order();
+
     let tc = game.canvas.texture_creator();
 
+    // Layout of the workshop
+    let mut workshop = Workshop::load(Path::new("assets/workshop.txt")).unwrap();
+
     let floor: Texture = tc.load_texture(Path::new("assets/Floor.png")).unwrap();
-    let anvil: Texture = tc.load_texture(Path::new("assets/Anvil.png")).unwrap();
-    let forge: Texture = tc.load_texture(Path::new("assets/Forge.png")).unwrap();
-    let desk: Texture = tc.load_texture(Path::new("assets/Desk.png")).unwrap();
     let p: Texture = tc.load_texture(Path::new("assets/Player.png")).unwrap();
 
-    let anvil_rect = Rect::new(389, 288, 120, 120);
-    let forge_rect = Rect::new(319, -59, 201, 219);
-    let desk_rect = Rect::new(69, 152, 125, 250);
+    // One texture per kind of station
+    let mut station_textures: HashMap<StationKind, Texture> = HashMap::new();
+    for station in &workshop.stations {
+        station_textures.entry(station.kind).or_insert_with(|| {
+            tc.load_texture(Path::new(station.kind.texture_path()))
+                .unwrap()
+        });
+    }
+
     let mut p_rect = Rect::new(224, 178, 120, 120);
 
+    // Font for everything drawn over the workshop
+    let mut hud_font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 16)
+        .unwrap();
+
     game.canvas.set_draw_color(Color::RGB(0, 255, 255));
     game.canvas.clear();
-    game.canvas.present();
+    game.toasts.present(&mut game.canvas);
 
     // Track number of frames run and whether to keep running
     let mut i = 0;
-    let mut days = 0;
     let mut run = true;
+
+    // Last hour customers were let in for
+    let mut hour = game.state.time / 60;
+
+    // Where a click sent the player, and whether to use a station there
+    let mut walk: Option<((i32, i32), Option<usize>)> = None;
     while run {
         // Handle time system
         i += 1;
-        if i >= 3600 {
+        if i % game.settings.frames_per_minute() == 0 {
+            game.state.time += 1;
+        }
+
+        // Customers come by once an hour while the shop is open. Hours
+        // skipped by sleeping or long jobs are each rolled here too
+        while hour < game.state.time / 60 {
+            hour += 1;
+            if shop_open(game.state.day, hour * 60) {
+                let made = sell_from_rack(&mut game);
+                if made > 0 {
+                    notify(
+                        &mut game,
+                        &format!("Sold from rack: {}$", made),
+                        Severity::Success,
+                    );
+                }
+            }
+        }
+
+        if game.state.time >= DAY_END {
+            // Rent, fuel and wages are due every night
+            game.state.pay_upkeep();
+
+            // Write the books up at the end of every day
+            if save_ledger(&game.state.ledger).is_err() {
+                display_error(&mut game, "Couldn't save the ledger");
+            }
+
+            // Customers give up on orders due today
+            for order in game.state.expire_orders() {
+                notify(
+                    &mut game,
+                    &format!("Missed order: {}", order),
+                    Severity::Warning,
+                );
+            }
+
+            // Sum up the day before starting the next
+            day_report(&mut game);
+            game.state.end_day();
+            hour = game.state.time / 60;
+
+            if let Some(order) = game.state.take_order() {
+                notify(&mut game, &format!("New order: {}", order), Severity::Info);
+            }
+
+            // Start counting frames again so the counter never overflows
             i = 0;
-            days += 1;
 
-            // End condition (for now)
-            if days == 5 {
-                let m = game.state.money;
-                continue_screen(&mut game, vec!["Two weeks have passed", &format!("You made {}$", m)]);
+            // Bankruptcy, the deadline or hitting every target ends the run
+            let outcome = scenario.outcome(&game.state);
+            if outcome != Outcome::Playing {
+                campaign_over(&mut game, scenario, outcome);
                 return;
             }
-
-            // Alert user
-            display_error(&mut game, "A day has passed");
         }
 
         // Update player
-        update_player_rect(&controls, &mut p_rect);
+        workshop.update(&game.state.upgrades);
+        workshop.move_player(&controls, &mut p_rect);
 
         // Update items
-        update_temp(&mut game);
+        update_temp(&mut game, i);
 
         // Handle events
-        run = handle_events(&mut controls, &mut game.event_pump);
+        run = handle_events(&mut controls, &mut game.input);
+
+        // Click on the floor to walk there, or on a station to go use it
+        if let Some((x, y)) = controls.click.take() {
+            walk = match workshop.station_under(x, y) {
+                Some(s) => {
+                    let c = workshop.stations[s].zone.center();
+                    Some(((c.x(), c.y()), Some(s)))
+                }
+                None => Some(((x, y), None)),
+            };
+        }
+
+        // Keys take over from the mouse
+        if controls.up || controls.down || controls.left || controls.right {
+            walk = None;
+        }
+
+        if let Some((target, station)) = walk {
+            let moving = workshop.walk_towards(target, &mut p_rect);
+            let feet = player_feet(p_rect);
+            let arrived = station.is_some_and(|s| workshop.stations[s].zone.has_intersection(feet));
+            if arrived {
+                controls.enter = true;
+            }
+            if arrived || !moving {
+                walk = None;
+            }
+        }
 
         // Interact button
         if controls.enter {
             controls.enter = false;
-            if let Some(_) = p_rect.intersection(anvil_rect) {
-                // Run anvil minigame
-                if run_anvil(&mut game) {
-                    // Hammering always takes 1/3 day
-                    i += 20 * 60;
-                }
-            } else if let Some(_) = p_rect.intersection(desk_rect) {
-                // Test inventory
-                display_inventory(&mut game, None);
-            } else if let Some(_) = p_rect.intersection(forge_rect) {
-                // Choose what to put in
-                move_to_furnace(&mut game);
+            if let Some(kind) = workshop.station_at(p_rect).map(|s| s.kind) {
+                use_station(&mut game, kind);
             }
         }
 
         // Draw images
         game.canvas.copy(&floor, None, None).unwrap();
-        game.canvas.copy(&anvil, None, Some(anvil_rect)).unwrap();
-        game.canvas.copy(&forge, None, Some(forge_rect)).unwrap();
-        game.canvas.copy(&desk, None, Some(desk_rect)).unwrap();
+        for station in workshop.built() {
+            game.canvas
+                .copy(&station_textures[&station.kind], None, Some(station.sprite))
+                .unwrap();
+        }
         game.canvas.copy(&p, None, Some(p_rect)).unwrap();
 
         // Borrow error? (Rect implements Copy)
         game.canvas.copy(&p, None, Some(p_rect)).unwrap();
+        draw_hud(&mut game, &mut hud_font);
+
+        // Prompt for the station the player is at, tooltip for the one under the mouse
+        let at = workshop.station_at(p_rect);
+        if let Some(s) = at {
+            draw_prompt(&mut game, &mut hud_font, s);
+        }
+        if let Some((x, y)) = controls.mouse {
+            if let Some(s) = workshop.station_under(x, y).map(|i| &workshop.stations[i]) {
+                if at.map(|a| a.kind) != Some(s.kind) {
+                    draw_tooltip(&mut game, &mut hud_font, s, (x, y));
+                }
+            }
+        }
 
         // Update
-        game.canvas.present();
+        game.toasts.present(&mut game.canvas);
 
         // Sleep
         ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
+
+    // Keep what was bought and sold today too. The window is closing, so
+    // there's nowhere left to report a failure
+    save_ledger(&game.state.ledger).ok();
 }
@@ -0,0 +1,166 @@
+use super::calendar::*;
+use super::game::*;
+use super::toast::*;
+use super::ui::*;
+use crate::handle_events;
+use crate::Controls;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+
+use std::time::Duration;
+
+// Temperature bar, from room temp on the left to the item's start on the right
+const BAR: (i32, i32, u32, u32) = (50, 200, 500, 40);
+
+// Where on the bar a temperature sits
+fn bar_x(temp: i32, start: i32) -> i32 {
+    let span = (start - 70).max(1);
+    BAR.0 + (BAR.2 as i32) * (temp - 70) / span
+}
+
+// 100 in the middle of the range, falling off to 0 well outside it
+fn quench_accuracy(temp: i32, range: (i32, i32)) -> i32 {
+    let mid = (range.0 + range.1) / 2;
+    let half = (range.1 - range.0) / 2;
+    let off = (temp - mid).abs();
+
+    if off <= half {
+        100 - 20 * off / half
+    } else {
+        (80 - 80 * (off - half) / (2 * half)).max(0)
+    }
+}
+
+// Plunge a hot item into the tub and pull it out at the right moment
+pub fn run_quench(game: &mut Game, index: usize) {
+    let item = game.state.inventory[index].clone();
+    let range = item.material.quench_range();
+    let start = item.temp;
+
+    game.state.inventory[index].location = Location::Tub;
+
+    let tc = game.canvas.texture_creator();
+    let mut font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 24)
+        .unwrap();
+
+    let title = create_text(
+        "Press ENTER to pull it out",
+        &tc,
+        &mut font,
+        Color::RGB(255, 255, 255),
+    );
+    let title_rect = center_text(Rect::new(0, 80, SCREEN_WIDTH, 80), &title);
+
+    let bar = Rect::new(BAR.0, BAR.1, BAR.2, BAR.3);
+    let target = Rect::new(
+        bar_x(range.0, start),
+        BAR.1,
+        (bar_x(range.1, start) - bar_x(range.0, start)) as u32,
+        BAR.3,
+    );
+
+    let mut cs = Controls::new();
+    let mut temp = start;
+
+    loop {
+        // Handle events
+        if !handle_events(&mut cs, &mut game.input) {
+            break;
+        }
+        if cs.enter {
+            break;
+        }
+
+        // Water pulls heat out fast at first, then slows down
+        temp -= (temp - 70) / 90 + 2;
+        if temp <= 70 {
+            temp = 70;
+            break;
+        }
+
+        // Draw
+        game.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        game.canvas.clear();
+
+        game.canvas.copy(&title, None, Some(title_rect)).unwrap();
+
+        game.canvas.set_draw_color(Color::RGB(60, 60, 60));
+        game.canvas.fill_rect(bar).unwrap();
+        game.canvas.set_draw_color(Color::RGB(119, 235, 52));
+        game.canvas.fill_rect(target).unwrap();
+
+        game.canvas.set_draw_color(Color::RGB(235, 204, 52));
+        game.canvas
+            .fill_rect(Rect::new(bar_x(temp, start) - 2, BAR.1 - 10, 4, BAR.3 + 20))
+            .unwrap();
+
+        let t = create_text(
+            &format!("{}", temp),
+            &tc,
+            &mut font,
+            Color::RGB(255, 255, 255),
+        );
+        let r = center_text(Rect::new(0, 280, SCREEN_WIDTH, 60), &t);
+        game.canvas.copy(&t, None, Some(r)).unwrap();
+
+        game.toasts.present(&mut game.canvas);
+
+        // Sleep
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+
+    // Done drawing, let go of the font
+    drop(font);
+
+    // Only hardenable metals that went in hot enough get anything from it
+    let hardness = match item.material.harden_temp() {
+        Some(h) if start >= h => quench_accuracy(temp, range),
+        _ => 0,
+    };
+
+    let p = &mut game.state.inventory[index];
+    p.temp = temp;
+    p.location = Location::Storage;
+    p.hardness = hardness;
+    p.history.push(Step::Quench(hardness));
+    p.appraise();
+    game.state.spend_time(Activity::Quench);
+
+    let message = if item.material.harden_temp().is_none() {
+        format!("{} doesn't harden", item.material)
+    } else if hardness == 0 {
+        String::from("Too cold to harden")
+    } else {
+        format!("Hardness: {}", hardness)
+    };
+    notify(game, &message, Severity::Info);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accuracy_inside_the_range() {
+        let range = (300, 500);
+        assert_eq!(quench_accuracy(400, range), 100);
+        assert_eq!(quench_accuracy(450, range), 90);
+        assert_eq!(quench_accuracy(350, range), 90);
+        assert_eq!(quench_accuracy(300, range), 80);
+        assert_eq!(quench_accuracy(500, range), 80);
+    }
+
+    #[test]
+    fn accuracy_falls_off_outside() {
+        let range = (300, 500);
+        assert_eq!(quench_accuracy(299, range), 80);
+        assert_eq!(quench_accuracy(600, range), 40);
+        assert_eq!(quench_accuracy(200, range), 40);
+        assert_eq!(quench_accuracy(100, range), 0);
+        assert_eq!(quench_accuracy(70, range), 0);
+        assert_eq!(quench_accuracy(900, range), 0);
+    }
+}
@@ -0,0 +1,69 @@
+use super::game::*;
+use super::scenario::*;
+use super::ui::*;
+
+use sdl2::pixels::Color;
+
+// Summary of the day that just ended, shown before the next one starts
+pub fn day_report(game: &mut Game) {
+    let state = &game.state;
+    let income = state.income(state.day);
+    let expenses = state.expenses(state.day);
+    let net = income - expenses;
+    let rep = state.reputation - state.today.reputation;
+    let best = match state.today.best_score {
+        Some(s) => s.to_string(),
+        None => String::from("-"),
+    };
+
+    let white = Color::RGB(255, 255, 255);
+    let green = Color::RGB(119, 235, 52);
+    let red = Color::RGB(235, 52, 52);
+
+    let mut lines = vec![
+        (format!("Income: {}$", income), white),
+        (format!("Expenses: {}$", expenses), white),
+        (
+            format!("Net: {:+}$", net),
+            if net >= 0 { green } else { red },
+        ),
+        (format!("Items crafted: {}", state.today.crafted), white),
+        (format!("Best anvil score: {}", best), white),
+        (
+            format!("Reputation: {} ({:+})", state.reputation, rep),
+            if rep >= 0 { green } else { red },
+        ),
+    ];
+    if state.debt > 0 {
+        lines.push((format!("Owed to the bank: {}$", state.debt), red));
+    }
+    let title = format!("Day {} is over", state.day + 1);
+
+    continue_screen(game, &title, white, &lines);
+}
+
+// Victory or defeat, with how the run's targets went
+pub fn campaign_over(game: &mut Game, scenario: &Scenario, outcome: Outcome) {
+    let white = Color::RGB(255, 255, 255);
+    let green = Color::RGB(119, 235, 52);
+    let red = Color::RGB(235, 52, 52);
+
+    let (title, colour) = match outcome {
+        Outcome::Victory => ("Victory!", green),
+        _ => ("Defeat", red),
+    };
+
+    let reason = if game.state.bankrupt() {
+        format!("You went bankrupt owing {}$", game.state.debt)
+    } else {
+        format!("{} after {} days", scenario.name, game.state.day)
+    };
+
+    let mut lines = vec![(reason, white)];
+    for (goal, met) in scenario.goals(&game.state) {
+        lines.push((goal, if met { green } else { red }));
+    }
+    lines.push((format!("Money: {}$", game.state.money), white));
+
+    continue_screen(game, title, colour, &lines);
+}
@@ -0,0 +1,329 @@
+use super::game::*;
+use super::text::*;
+use super::ui::*;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Texture;
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+// Rules for one run, read from assets/scenarios.txt
+pub struct Scenario {
+    pub name: String,
+    pub description: String,
+    // Length of the run, None for no limit
+    pub days: Option<i32>,
+    // Targets that all have to be met to win
+    pub money: Option<i32>,
+    pub reputation: Option<i32>,
+    pub masterpieces: Option<i32>,
+    pub start_money: i32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Playing,
+    Victory,
+    Defeat,
+}
+
+impl Scenario {
+    fn new(name: &str) -> Scenario {
+        Scenario {
+            name: String::from(name),
+            description: String::new(),
+            days: None,
+            money: None,
+            reputation: None,
+            masterpieces: None,
+            start_money: 100,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Vec<Scenario>, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        Scenario::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Vec<Scenario>, String> {
+        let mut scenarios: Vec<Scenario> = Vec::new();
+
+        for (i, raw) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = raw.trim();
+
+            // Skip blanks + comments
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // Keyword, then the rest of the line
+            let (key, rest) = match line.split_once(' ') {
+                Some((k, r)) => (k, r.trim()),
+                None => (line, ""),
+            };
+
+            if key == "scenario" {
+                if rest.is_empty() {
+                    return Err(format!("line {}: scenario needs a name", line_no));
+                }
+                scenarios.push(Scenario::new(rest));
+                continue;
+            }
+
+            let s = scenarios
+                .last_mut()
+                .ok_or(format!("line {}: '{}' outside a scenario", line_no, key))?;
+
+            if key == "description" {
+                s.description = String::from(rest);
+                continue;
+            }
+
+            let n: i32 = rest
+                .parse()
+                .map_err(|_| format!("line {}: bad number '{}'", line_no, rest))?;
+            match key {
+                "days" => s.days = Some(n),
+                "money" => s.money = Some(n),
+                "reputation" => s.reputation = Some(n),
+                "masterpieces" => s.masterpieces = Some(n),
+                "start_money" => s.start_money = n,
+                k => return Err(format!("line {}: unknown keyword '{}'", line_no, k)),
+            }
+        }
+
+        if scenarios.is_empty() {
+            return Err(String::from("no scenarios"));
+        }
+        Ok(scenarios)
+    }
+
+    fn has_targets(&self) -> bool {
+        self.money.is_some() || self.reputation.is_some() || self.masterpieces.is_some()
+    }
+
+    // Each target and whether it has been reached
+    pub fn goals(&self, state: &GameState) -> Vec<(String, bool)> {
+        let mut goals = Vec::new();
+        // Borrowed money doesn't count towards the target
+        if let Some(m) = self.money {
+            let worth = state.net_worth();
+            goals.push((format!("Net worth: {}/{}$", worth, m), worth >= m));
+        }
+        if let Some(r) = self.reputation {
+            goals.push((
+                format!("Reputation: {}/{}", state.reputation, r),
+                state.reputation >= r,
+            ));
+        }
+        if let Some(m) = self.masterpieces {
+            goals.push((
+                format!("Masterpieces: {}/{}", state.masterpieces, m),
+                state.masterpieces >= m,
+            ));
+        }
+        goals
+    }
+
+    // Checked at the end of each day
+    pub fn outcome(&self, state: &GameState) -> Outcome {
+        if state.bankrupt() {
+            return Outcome::Defeat;
+        }
+
+        let met = self.goals(state).iter().all(|g| g.1);
+        if self.has_targets() && met {
+            return Outcome::Victory;
+        }
+
+        match self.days {
+            Some(d) if state.day >= d => {
+                if met {
+                    Outcome::Victory
+                } else {
+                    Outcome::Defeat
+                }
+            }
+            _ => Outcome::Playing,
+        }
+    }
+}
+
+// New Game: pick which rules to play by
+pub fn pick_scenario(game: &mut Game, scenarios: &[Scenario]) -> Option<usize> {
+    // Load font
+    let mut font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 32)
+        .unwrap();
+
+    // Create tc
+    let tc = game.canvas.texture_creator();
+
+    let label = create_text("New Game", &tc, &mut font, Color::RGB(255, 255, 255));
+
+    font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 24)
+        .unwrap();
+    let mut small_font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 16)
+        .unwrap();
+
+    // Name on the list, rules underneath for the highlighted one
+    let mut names = Vec::<Texture>::new();
+    let mut details = Vec::<String>::new();
+    for s in scenarios {
+        names.push(create_text(
+            &s.name,
+            &tc,
+            &mut font,
+            Color::RGB(255, 255, 255),
+        ));
+
+        let length = match s.days {
+            Some(d) => format!("{} days", d),
+            None => String::from("No time limit"),
+        };
+        details.push(format!("{}  ({})", s.description, length));
+    }
+
+    let rows: Vec<Rect> = (0..scenarios.len() as i32)
+        .map(|i| Rect::new(100, 100 + 50 * i, 400, 50))
+        .collect();
+    let mut active: i32 = 0;
+
+    loop {
+        match handle_selection(&mut game.input, &mut active, &rows) {
+            HandlerRet::Exit => return None,
+            HandlerRet::Accept if active >= 0 => return Some(active as usize),
+            _ => (),
+        };
+
+        // Draw
+        game.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        game.canvas.clear();
+
+        let label_rect = center_text(Rect::new(0, 20, SCREEN_WIDTH, 60), &label);
+        game.canvas.copy(&label, None, Some(label_rect)).unwrap();
+
+        if active >= 0 {
+            game.canvas.set_draw_color(Color::RGB(50, 50, 50));
+            game.canvas.fill_rect(rows[active as usize]).unwrap();
+
+            // Long descriptions wrap onto more lines
+            draw_string(
+                &mut game.canvas,
+                &mut small_font,
+                &details[active as usize],
+                Color::RGB(200, 200, 200),
+                Rect::new(40, 400, 520, 70),
+                &TextStyle::centered(),
+            );
+        }
+
+        for (i, t) in names.iter().enumerate() {
+            let r = center_text(rows[i], t);
+            game.canvas.copy(t, None, Some(r)).unwrap();
+        }
+
+        game.toasts.present(&mut game.canvas);
+
+        // Sleep
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scenarios() {
+        let text = "\
+# Comment
+scenario Short run
+description Quick money
+days 3
+money 200
+
+scenario Sandbox
+start_money 50
+";
+        let s = Scenario::parse(text).unwrap();
+        assert_eq!(s.len(), 2);
+        assert_eq!(s[0].name, "Short run");
+        assert_eq!(s[0].description, "Quick money");
+        assert_eq!(s[0].days, Some(3));
+        assert_eq!(s[0].money, Some(200));
+        assert_eq!(s[0].start_money, 100);
+        assert_eq!(s[1].days, None);
+        assert_eq!(s[1].start_money, 50);
+    }
+
+    #[test]
+    fn shipped_scenarios_parse() {
+        assert!(!Scenario::parse(include_str!("../assets/scenarios.txt"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_scenarios() {
+        let bad = [
+            "",
+            "# Only comments\n",
+            "days 3\n",
+            "scenario\n",
+            "scenario A\ndays three\n",
+            "scenario A\nmoney\n",
+            "scenario A\ngold 10\n",
+        ];
+        for text in bad {
+            assert!(Scenario::parse(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn outcome_at_the_deadline() {
+        let s = &Scenario::parse("scenario A\ndays 3\nmoney 200\n").unwrap()[0];
+        let mut state = GameState::new();
+        state.day = 2;
+        state.money = 500;
+        assert!(s.outcome(&state) == Outcome::Victory);
+
+        state.money = 100;
+        assert!(s.outcome(&state) == Outcome::Playing);
+        state.day = 3;
+        assert!(s.outcome(&state) == Outcome::Defeat);
+
+        state.money = 500;
+        state.debt = LOAN_LIMIT + 1;
+        assert!(s.outcome(&state) == Outcome::Defeat);
+    }
+
+    #[test]
+    fn loans_dont_win() {
+        let s = &Scenario::parse(
+            "scenario A
+days 5
+money 500
+",
+        )
+        .unwrap()[0];
+        let mut state = GameState::new();
+        state.day = 1;
+        state.money = 600;
+        state.debt = 500;
+        assert!(s.outcome(&state) == Outcome::Playing);
+
+        state.debt = 100;
+        assert!(s.outcome(&state) == Outcome::Victory);
+    }
+}
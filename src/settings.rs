@@ -0,0 +1,537 @@
+use super::game::*;
+use super::input::*;
+use super::toast::*;
+use super::ui::*;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Texture;
+use sdl2::video::{FullscreenType, WindowPos};
+
+use std::fmt;
+use std::fs;
+use std::time::Duration;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Speed {
+    Slow,
+    Normal,
+    Fast,
+}
+
+impl Speed {
+    pub fn from_name(s: &str) -> Option<Speed> {
+        match s {
+            "slow" => Some(Speed::Slow),
+            "normal" => Some(Speed::Normal),
+            "fast" => Some(Speed::Fast),
+            _ => None,
+        }
+    }
+    fn name(&self) -> &'static str {
+        match self {
+            Speed::Slow => "slow",
+            Speed::Normal => "normal",
+            Speed::Fast => "fast",
+        }
+    }
+    // One step slower or faster, stopping at the ends
+    fn step(&self, dir: i32) -> Speed {
+        match (self, dir < 0) {
+            (Speed::Fast, true) => Speed::Normal,
+            (Speed::Normal, true) | (Speed::Slow, true) => Speed::Slow,
+            (Speed::Slow, false) => Speed::Normal,
+            (Speed::Normal, false) | (Speed::Fast, false) => Speed::Fast,
+        }
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Speed::Slow => "Slow",
+            Speed::Normal => "Normal",
+            Speed::Fast => "Fast",
+        })
+    }
+}
+
+// How the window covers the screen
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WindowMode {
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl WindowMode {
+    pub fn from_name(s: &str) -> Option<WindowMode> {
+        match s {
+            "windowed" => Some(WindowMode::Windowed),
+            "borderless" => Some(WindowMode::Borderless),
+            "fullscreen" => Some(WindowMode::Fullscreen),
+            _ => None,
+        }
+    }
+    fn name(&self) -> &'static str {
+        match self {
+            WindowMode::Windowed => "windowed",
+            WindowMode::Borderless => "borderless",
+            WindowMode::Fullscreen => "fullscreen",
+        }
+    }
+    // Cycles round in either direction
+    fn step(&self, dir: i32) -> WindowMode {
+        let modes = [
+            WindowMode::Windowed,
+            WindowMode::Borderless,
+            WindowMode::Fullscreen,
+        ];
+        let i = modes.iter().position(|m| m == self).unwrap() as i32;
+        modes[(i + dir).rem_euclid(3) as usize]
+    }
+    // Borderless is SDL's "desktop" fullscreen, which keeps the desktop resolution
+    pub fn fullscreen_type(&self) -> FullscreenType {
+        match self {
+            WindowMode::Windowed => FullscreenType::Off,
+            WindowMode::Borderless => FullscreenType::Desktop,
+            WindowMode::Fullscreen => FullscreenType::True,
+        }
+    }
+}
+
+impl fmt::Display for WindowMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            WindowMode::Windowed => "Windowed",
+            WindowMode::Borderless => "Borderless",
+            WindowMode::Fullscreen => "Fullscreen",
+        })
+    }
+}
+
+// Biggest size the window can be set to, in multiples of the screen
+pub const MAX_WINDOW_SCALE: u32 = 3;
+
+// Settings older files may still have, which no longer do anything
+const LEGACY: [&str; 3] = ["master_volume", "music_volume", "effects_volume"];
+
+// Every setting, in the order they're listed on the settings screen
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    WindowMode,
+    WindowScale,
+    IntegerScale,
+    Vsync,
+    Mouse,
+    ClockSpeed,
+    NoteSpeed,
+}
+
+impl Field {
+    pub fn all() -> Vec<Field> {
+        vec![
+            Field::WindowMode,
+            Field::WindowScale,
+            Field::IntegerScale,
+            Field::Vsync,
+            Field::Mouse,
+            Field::ClockSpeed,
+            Field::NoteSpeed,
+        ]
+    }
+    pub fn section(&self) -> &'static str {
+        match self {
+            Field::WindowMode | Field::WindowScale | Field::IntegerScale | Field::Vsync => {
+                "Display"
+            }
+            Field::Mouse => "Input",
+            Field::ClockSpeed => "Gameplay",
+            Field::NoteSpeed => "Accessibility",
+        }
+    }
+    // Name used in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::WindowMode => "window_mode",
+            Field::WindowScale => "window_scale",
+            Field::IntegerScale => "integer_scale",
+            Field::Vsync => "vsync",
+            Field::Mouse => "mouse",
+            Field::ClockSpeed => "clock_speed",
+            Field::NoteSpeed => "note_speed",
+        }
+    }
+    pub fn from_name(s: &str) -> Option<Field> {
+        Field::all().into_iter().find(|f| f.name() == s)
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Field::WindowMode => "Window",
+            Field::WindowScale => "Window size",
+            Field::IntegerScale => "Pixel perfect scaling",
+            Field::Vsync => "Vsync (on restart)",
+            Field::Mouse => "Mouse",
+            Field::ClockSpeed => "Clock speed",
+            Field::NoteSpeed => "Note speed",
+        })
+    }
+}
+
+pub struct Settings {
+    // Display
+    pub window_mode: WindowMode,
+    // Windowed size, in multiples of the 600x480 screen
+    pub window_scale: u32,
+    // Only scale by whole numbers, leaving black bars round the rest
+    pub integer_scale: bool,
+    pub vsync: bool,
+    // Input
+    pub mouse: bool,
+    // Gameplay
+    pub clock_speed: Speed,
+    // Accessibility
+    pub note_speed: Speed,
+}
+
+fn on_off(b: bool) -> String {
+    if b {
+        String::from("On")
+    } else {
+        String::from("Off")
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            window_mode: WindowMode::Windowed,
+            window_scale: 1,
+            integer_scale: false,
+            vsync: false,
+            mouse: true,
+            clock_speed: Speed::Normal,
+            note_speed: Speed::Normal,
+        }
+    }
+}
+
+impl Settings {
+    // Frames it takes the clock to move a minute
+    pub fn frames_per_minute(&self) -> i32 {
+        match self.clock_speed {
+            Speed::Slow => 6,
+            Speed::Normal => 4,
+            Speed::Fast => 2,
+        }
+    }
+
+    // How far anvil notes and grinding strokes fall each frame
+    pub fn note_step(&self) -> i32 {
+        match self.note_speed {
+            Speed::Slow => 3,
+            Speed::Normal => 5,
+            Speed::Fast => 7,
+        }
+    }
+
+    // How the setting reads on screen
+    pub fn value(&self, field: Field) -> String {
+        match field {
+            Field::WindowMode => self.window_mode.to_string(),
+            Field::WindowScale => format!("{}x", self.window_scale),
+            Field::IntegerScale => on_off(self.integer_scale),
+            Field::Vsync => on_off(self.vsync),
+            Field::Mouse => on_off(self.mouse),
+            Field::ClockSpeed => self.clock_speed.to_string(),
+            Field::NoteSpeed => self.note_speed.to_string(),
+        }
+    }
+
+    // Nudge a setting left or right, on/off settings just flip
+    pub fn change(&mut self, field: Field, dir: i32) {
+        match field {
+            Field::WindowMode => self.window_mode = self.window_mode.step(dir),
+            Field::WindowScale => {
+                self.window_scale =
+                    (self.window_scale as i32 + dir).clamp(1, MAX_WINDOW_SCALE as i32) as u32
+            }
+            Field::IntegerScale => self.integer_scale = !self.integer_scale,
+            Field::Vsync => self.vsync = !self.vsync,
+            Field::Mouse => self.mouse = !self.mouse,
+            Field::ClockSpeed => self.clock_speed = self.clock_speed.step(dir),
+            Field::NoteSpeed => self.note_speed = self.note_speed.step(dir),
+        }
+    }
+
+    // Lines of "setting = value", anything missing keeps its default
+    pub fn parse(text: &str) -> Result<Settings, String> {
+        let mut s = Settings::default();
+
+        for (i, raw) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = raw.trim();
+
+            // Skip blanks + comments
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, value) = line
+                .split_once('=')
+                .ok_or(format!("line {}: expected setting = value", line_no))?;
+            let (name, value) = (name.trim(), value.trim());
+
+            // Volumes were saved before the game had any sound, pass over them
+            if LEGACY.contains(&name) {
+                continue;
+            }
+            let field = Field::from_name(name)
+                .ok_or(format!("line {}: unknown setting '{}'", line_no, name))?;
+
+            let bad = || format!("line {}: bad value '{}' for {}", line_no, value, name);
+            let flag = || match value {
+                "on" => Ok(true),
+                "off" => Ok(false),
+                _ => Err(bad()),
+            };
+            let speed = || Speed::from_name(value).ok_or(bad());
+            let scale = || match value.parse::<u32>() {
+                Ok(v) if (1..=MAX_WINDOW_SCALE).contains(&v) => Ok(v),
+                _ => Err(bad()),
+            };
+
+            match field {
+                Field::WindowMode => s.window_mode = WindowMode::from_name(value).ok_or(bad())?,
+                Field::WindowScale => s.window_scale = scale()?,
+                Field::IntegerScale => s.integer_scale = flag()?,
+                Field::Vsync => s.vsync = flag()?,
+                Field::Mouse => s.mouse = flag()?,
+                Field::ClockSpeed => s.clock_speed = speed()?,
+                Field::NoteSpeed => s.note_speed = speed()?,
+            }
+        }
+
+        Ok(s)
+    }
+
+    pub fn to_text(&self) -> String {
+        let flag = |b: bool| if b { "on" } else { "off" };
+
+        let mut s = String::from("# Game settings\n");
+        s.push_str(&format!("window_mode = {}\n", self.window_mode.name()));
+        s.push_str(&format!("window_scale = {}\n", self.window_scale));
+        s.push_str(&format!("integer_scale = {}\n", flag(self.integer_scale)));
+        s.push_str(&format!("vsync = {}\n", flag(self.vsync)));
+        s.push_str(&format!("mouse = {}\n", flag(self.mouse)));
+        s.push_str(&format!("clock_speed = {}\n", self.clock_speed.name()));
+        s.push_str(&format!("note_speed = {}\n", self.note_speed.name()));
+        s
+    }
+
+    // Saved settings, or the defaults if there are none yet. A file that
+    // can't be read is an error, so the player can be told
+    pub fn load() -> Result<Settings, String> {
+        match fs::read_to_string(config_dir().join("settings.txt")) {
+            Ok(text) => Settings::parse(&text).map_err(|e| format!("settings.txt {}", e)),
+            Err(_) => Ok(Settings::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let dir = config_dir();
+        fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+        let path = dir.join("settings.txt");
+        fs::write(&path, self.to_text()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+// Put whatever can change while running into effect. The window is only
+// touched when its own settings changed, so a resize by hand sticks. None
+// applies everything, for startup
+pub fn apply_settings(game: &mut Game, changed: Option<Field>) {
+    let settings = &game.settings;
+    let window = game.canvas.window_mut();
+    let window_changed = matches!(
+        changed,
+        None | Some(Field::WindowMode) | Some(Field::WindowScale)
+    );
+    if window_changed
+        && window
+            .set_fullscreen(settings.window_mode.fullscreen_type())
+            .is_err()
+    {
+        game.toasts
+            .push("Couldn't change window mode", Severity::Error);
+    }
+    if window_changed && settings.window_mode == WindowMode::Windowed {
+        let (w, h) = (
+            SCREEN_WIDTH * settings.window_scale,
+            SCREEN_HEIGHT * settings.window_scale,
+        );
+        if window.size() != (w, h) {
+            window.set_size(w, h).unwrap();
+            window.set_position(WindowPos::Centered, WindowPos::Centered);
+        }
+    }
+
+    // The canvas letterboxes the screen into whatever size the window is
+    game.canvas
+        .set_integer_scale(game.settings.integer_scale)
+        .unwrap();
+
+    game.input.mouse = game.settings.mouse;
+}
+
+// Settings grouped by section, Left/Right or Enter to change one
+pub fn settings_screen(game: &mut Game) {
+    let fields = Field::all();
+
+    // One row per setting, then the controls screen
+    let rows: Vec<Rect> = (0..fields.len() as i32 + 1)
+        .map(|i| Rect::new(20, 20 + 36 * i, 560, 36))
+        .collect();
+    let mut active: i32 = 0;
+
+    // Load font
+    let mut font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 20)
+        .unwrap();
+
+    loop {
+        let mut dir = 0;
+        let mut exit = false;
+        for e in game.input.poll() {
+            match e {
+                InputEvent::Quit | InputEvent::Press(Action::Back) => exit = true,
+                InputEvent::Press(Action::MoveUp) if active > 0 => active -= 1,
+                InputEvent::Press(Action::MoveDown) if active + 1 < rows.len() as i32 => {
+                    active += 1
+                }
+                InputEvent::Press(Action::MoveLeft) => dir = -1,
+                InputEvent::Press(Action::MoveRight) | InputEvent::Press(Action::Interact) => {
+                    dir = 1
+                }
+                InputEvent::MouseMove(x, y) => {
+                    if let Some(i) = row_at(&rows, x, y) {
+                        active = i as i32;
+                    }
+                }
+                InputEvent::Click(x, y) => {
+                    if let Some(i) = row_at(&rows, x, y) {
+                        active = i as i32;
+                        dir = 1;
+                    }
+                }
+                _ => (),
+            }
+        }
+        if exit {
+            if game.settings.save().is_err() {
+                display_error(game, "Couldn't save settings");
+            }
+            return;
+        }
+
+        if dir != 0 {
+            if active == fields.len() as i32 {
+                controls_screen(game);
+            } else {
+                game.settings.change(fields[active as usize], dir);
+                apply_settings(game, Some(fields[active as usize]));
+            }
+            continue;
+        }
+
+        // Create tc
+        let tc = game.canvas.texture_creator();
+
+        let mut labels: Vec<String> = fields
+            .iter()
+            .map(|f| format!("{}  {}: {}", f.section(), f, game.settings.value(*f)))
+            .collect();
+        labels.push(String::from("Controls..."));
+
+        // Draw
+        game.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        game.canvas.clear();
+
+        if active >= 0 {
+            game.canvas.set_draw_color(Color::RGB(50, 50, 50));
+            game.canvas.fill_rect(rows[active as usize]).unwrap();
+        }
+
+        let mut texts = Vec::<Texture>::new();
+        for label in &labels {
+            texts.push(create_text(
+                label,
+                &tc,
+                &mut font,
+                Color::RGB(255, 255, 255),
+            ));
+        }
+        for (i, t) in texts.iter().enumerate() {
+            let r = Rect::new(40, rows[i].y() + 6, t.query().width, t.query().height);
+            game.canvas.copy(t, None, Some(r)).unwrap();
+        }
+
+        game.toasts.present(&mut game.canvas);
+
+        // Sleep
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_text() {
+        let s = Settings {
+            window_mode: WindowMode::Borderless,
+            window_scale: MAX_WINDOW_SCALE,
+            vsync: true,
+            mouse: false,
+            note_speed: Speed::Fast,
+            ..Default::default()
+        };
+
+        let text = s.to_text();
+        assert_eq!(Settings::parse(&text).unwrap().to_text(), text);
+    }
+
+    #[test]
+    fn missing_settings_keep_defaults() {
+        let s = Settings::parse("# Only one\nclock_speed = slow\n").unwrap();
+        assert!(s.clock_speed == Speed::Slow);
+        assert!(s.note_speed == Speed::Normal);
+        assert!(s.mouse);
+
+        // Volumes from before they were dropped are passed over
+        assert!(Settings::parse("master_volume = 50\n").is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        let bad = [
+            "vsync on\n",
+            "brightness = 50\n",
+            "vsync = yes\n",
+            "window_scale = 0\n",
+            "window_mode = tiny\n",
+            "clock_speed = warp\n",
+        ];
+        for text in bad {
+            assert!(Settings::parse(text).is_err(), "{:?}", text);
+        }
+
+        let too_big = format!("window_scale = {}\n", MAX_WINDOW_SCALE + 1);
+        assert!(Settings::parse(&too_big).is_err());
+    }
+}
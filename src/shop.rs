@@ -0,0 +1,352 @@
+use super::calendar::*;
+use super::game::*;
+use super::input::*;
+use super::text::*;
+use super::ui::*;
+use super::widgets::*;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+
+use std::time::Duration;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ShopTab {
+    Buy,
+    Sell,
+}
+
+// Items that are free to be sold
+fn sellable(game: &Game) -> Vec<usize> {
+    game.state
+        .inventory
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| x.location == Location::Storage || x.location == Location::Rack)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// Buy some number of goods, or say why not
+fn buy(game: &mut Game, goods: Goods, quantity: i32) -> Result<String, String> {
+    let cost = goods.unit_price() * quantity;
+    if cost > game.state.money {
+        return Err(String::from("Can't afford"));
+    }
+
+    let space = game.state.upgrades.storage_space - game.state.stored_weight();
+    if goods.storage() * quantity > space {
+        return Err(String::from("Not enough storage"));
+    }
+
+    match goods {
+        Goods::Metal(m) => game.state.add_metal(m, quantity),
+        Goods::Ore(o) => {
+            for _ in 0..quantity {
+                game.state.ores.push(o);
+            }
+        }
+        Goods::Part(p) => {
+            for _ in 0..quantity {
+                game.state.parts.push(p);
+            }
+        }
+    }
+
+    let description = match goods {
+        Goods::Metal(_) => format!("Bought {}u {}", quantity, goods),
+        _ => format!("Bought {} {}", quantity, goods),
+    };
+    game.state
+        .transact(TransactionKind::Purchase, description.clone(), -cost);
+    game.state.spend_time(Activity::Shop);
+    Ok(description)
+}
+
+// Ask before letting an item go
+fn confirm_sale(game: &mut Game, index: usize) -> bool {
+    let item = &game.state.inventory[index];
+    let question = format!("Sell {} for {}$?", item.name(), item.value);
+
+    let mut font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 24)
+        .unwrap();
+
+    let mut modal = Modal::new(500, 160, &question, &["Yes", "No"]);
+
+    loop {
+        for e in game.input.poll() {
+            match modal.handle(&e) {
+                HandlerRet::Exit => return false,
+                HandlerRet::Accept => return modal.active == 0,
+                HandlerRet::Nothing => (),
+            }
+        }
+
+        // Draw
+        modal.draw(&mut game.canvas, &mut font);
+
+        game.toasts.present(&mut game.canvas);
+
+        // Sleep
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+// Shop screen, buy goods or sell finished work
+pub fn shop_screen(game: &mut Game) {
+    let goods = Goods::all();
+    let grey = Color::RGB(200, 200, 200);
+    let green = Color::RGB(119, 235, 52);
+    let red = Color::RGB(235, 52, 52);
+
+    let mut tab = ShopTab::Buy;
+    let mut tabs = Tabs::new(Rect::new(0, 0, SCREEN_WIDTH, 70), &["Buy", "Sell"]);
+
+    // Five rows show at a time, the rest scroll
+    let mut list = List::new(Rect::new(20, 80, 560, 250), 50);
+    list.active = 0;
+    let mut quantity = goods[0].default_quantity();
+
+    let mut empty = Label::new("", Rect::new(40, 80, 520, 50));
+    empty.color = grey;
+    empty.set_text("Nothing to sell");
+    let mut status = Label::new("", Rect::new(20, 340, 560, 40));
+    let mut detail = Label::new("", Rect::new(20, 385, 560, 40));
+    let mut outcome = Label::new("", Rect::new(20, 425, 560, 40));
+
+    // Result of the last thing tried, and whether it went through
+    let mut message: Option<(String, bool)> = None;
+
+    // Sale waiting on confirmation
+    let mut selling: Option<usize> = None;
+
+    // Load fonts
+    let mut font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 32)
+        .unwrap();
+    let mut small_font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 24)
+        .unwrap();
+
+    loop {
+        // A sale picked last frame gets its popup before anything else
+        if let Some(index) = selling.take() {
+            if confirm_sale(game, index) {
+                let item = game.state.inventory.remove(index);
+                let description = format!("Sold {}", item.name());
+                game.state
+                    .transact(TransactionKind::Sale, description.clone(), item.value);
+                game.state.reputation += item.reputation();
+                game.state.spend_time(Activity::Shop);
+                message = Some((description, true));
+            }
+        }
+
+        let items = sellable(game);
+        list.set_items(match tab {
+            ShopTab::Buy => goods
+                .iter()
+                .map(|g| {
+                    let row = match g {
+                        Goods::Metal(_) => format!("{}: {}$/u", g, g.unit_price()),
+                        _ => format!("{}: {}$", g, g.unit_price()),
+                    };
+                    (row, TEXT)
+                })
+                .collect(),
+            ShopTab::Sell => items
+                .iter()
+                .map(|i| (game.state.inventory[*i].to_string(), TEXT))
+                .collect(),
+        });
+        if list.active < 0 && !list.items.is_empty() {
+            list.select(0);
+        }
+
+        // Quantities go up in steps of what's normally sold at once
+        let step = match tab {
+            ShopTab::Buy => goods[list.active.max(0) as usize].default_quantity(),
+            ShopTab::Sell => 1,
+        };
+        let before = list.active;
+
+        // Handle events
+        let mut exit = false;
+        let mut accept = false;
+        let mut switch_to: Option<ShopTab> = None;
+        for e in game.input.poll() {
+            if tabs.handle(&e) {
+                switch_to = match tabs.active {
+                    0 => Some(ShopTab::Buy),
+                    _ => Some(ShopTab::Sell),
+                };
+                continue;
+            }
+
+            match e {
+                InputEvent::Press(Action::MoveLeft) if quantity > step => quantity -= step,
+                InputEvent::Press(Action::MoveRight) => quantity += step,
+                _ => match list.handle(&e) {
+                    HandlerRet::Exit => exit = true,
+                    HandlerRet::Accept => accept = true,
+                    HandlerRet::Nothing => (),
+                },
+            }
+        }
+        if exit {
+            return;
+        }
+
+        if let Some(t) = switch_to {
+            if t != tab {
+                tab = t;
+                list.select(0);
+                list.scroll = 0;
+                quantity = goods[0].default_quantity();
+                message = None;
+            }
+            continue;
+        }
+
+        // New row, start again from its usual amount
+        if list.active != before && tab == ShopTab::Buy && list.active >= 0 {
+            quantity = goods[list.active as usize].default_quantity();
+        }
+
+        if accept && list.active >= 0 {
+            let active = list.active as usize;
+            match tab {
+                ShopTab::Buy => {
+                    message = Some(match buy(game, goods[active], quantity) {
+                        Ok(s) => (s, true),
+                        Err(s) => (s, false),
+                    });
+                }
+                ShopTab::Sell => {
+                    if active < items.len() {
+                        selling = Some(items[active]);
+                    }
+                }
+            }
+            continue;
+        }
+
+        // Money + storage
+        status.set_text(&format!(
+            "Money: {}$  Storage: {}/{}",
+            game.state.money,
+            game.state.stored_weight(),
+            game.state.upgrades.storage_space
+        ));
+
+        // What the current choice costs
+        detail.spans = match tab {
+            ShopTab::Buy if list.active >= 0 => {
+                let cost = goods[list.active as usize].unit_price() * quantity;
+                let c = if cost > game.state.money { red } else { TEXT };
+                vec![Span::new(
+                    &format!("Quantity: {}  Total: {}$  (Left/Right)", quantity, cost),
+                    c,
+                )]
+            }
+            _ => Vec::new(),
+        };
+
+        // Outcome of the last purchase or sale
+        outcome.spans = match &message {
+            Some((s, ok)) => vec![Span::new(s, if *ok { green } else { red })],
+            None => Vec::new(),
+        };
+
+        // Black background
+        game.canvas.set_draw_color(BACKGROUND);
+        game.canvas.clear();
+
+        // Tabs, the open one highlighted
+        tabs.draw(&mut game.canvas, &mut font);
+
+        list.draw(&mut game.canvas, &mut small_font);
+        if list.items.is_empty() {
+            empty.draw(&mut game.canvas, &mut small_font);
+        }
+        status.draw(&mut game.canvas, &mut small_font);
+        detail.draw(&mut game.canvas, &mut small_font);
+        outcome.draw(&mut game.canvas, &mut small_font);
+
+        // Update
+        game.toasts.present(&mut game.canvas);
+
+        // Sleep
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+// Every purchase and sale so far, newest first, under a heading per run
+pub fn display_ledger(game: &mut Game) {
+    let grey = Color::RGB(200, 200, 200);
+
+    // Load fonts
+    let mut big_font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 32)
+        .unwrap();
+    let mut font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 20)
+        .unwrap();
+
+    let label = Label::new("Ledger:", Rect::new(20, 20, 560, 50));
+
+    // One line per transaction
+    let mut lines: Vec<(String, Color)> = Vec::new();
+    let mut run = None;
+    for t in game.state.ledger.iter().rev() {
+        if run != Some(t.run) {
+            run = Some(t.run);
+            let heading = if t.run == game.state.run {
+                String::from("This run")
+            } else {
+                format!("Run {}", t.run + 1)
+            };
+            lines.push((heading, grey));
+        }
+        let c = if t.amount >= 0 {
+            Color::RGB(119, 235, 52)
+        } else {
+            Color::RGB(235, 52, 52)
+        };
+        let s = format!("Day {}  {}  {:+}$", t.day + 1, t.description, t.amount);
+        lines.push((s, c));
+    }
+    if lines.is_empty() {
+        lines.push((String::from("Nothing yet"), grey));
+    }
+
+    // Twelve rows fit between the label and the bottom
+    let mut list = List::new(Rect::new(20, 80, 560, 360), 30);
+    list.set_items(lines);
+
+    loop {
+        for e in game.input.poll() {
+            if let HandlerRet::Exit = list.handle(&e) {
+                return;
+            }
+        }
+
+        // Draw
+        game.canvas.set_draw_color(BACKGROUND);
+        game.canvas.clear();
+
+        label.draw(&mut game.canvas, &mut big_font);
+        list.draw(&mut game.canvas, &mut font);
+
+        game.toasts.present(&mut game.canvas);
+
+        // Sleep
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
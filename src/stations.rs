@@ -0,0 +1,816 @@
+use super::anvil::*;
+use super::calendar::*;
+use super::game::*;
+use super::grind::*;
+use super::quench::*;
+use super::settings::*;
+use super::shop::*;
+use super::toast::*;
+use super::ui::*;
+use super::workshop::*;
+
+use rand::Rng;
+
+// How many items fit on the display rack
+const RACK_SPACE: usize = 3;
+
+// A menu entry and what picking it does
+type StationAction = (String, fn(&mut Game));
+
+// Whether a station can be used right now, and anything worth knowing first
+pub enum StationState {
+    Ready,
+    // Usable, with something to point out
+    Note(String),
+    // Nothing to do there, and why
+    Disabled(String),
+}
+
+fn count_at(game: &Game, location: Location) -> usize {
+    game.state
+        .inventory
+        .iter()
+        .filter(|x| x.location == location)
+        .count()
+}
+
+pub fn station_state(game: &Game, kind: StationKind) -> StationState {
+    let inventory = &game.state.inventory;
+    match kind {
+        StationKind::Anvil => {
+            // Same checks run_anvil makes once an item is picked
+            let workable = inventory.iter().any(|x| {
+                let shape = matches!(x.form, Form::Bar) || x.next_step() == Some(StepKind::Hammer);
+                shape && !matches!(x.temp_val(), Temp::Under)
+            });
+            if workable {
+                StationState::Ready
+            } else {
+                StationState::Disabled(String::from("Nothing hot enough to hammer"))
+            }
+        }
+        StationKind::Forge => {
+            let used = count_at(game, Location::Forge);
+            let space = game.state.upgrades.forge_space;
+            if used as i32 >= space {
+                StationState::Note(format!("Forge full: {}/{}", used, space))
+            } else {
+                StationState::Ready
+            }
+        }
+        StationKind::QuenchTub => {
+            if inventory.iter().any(|x| x.temp > 70) {
+                StationState::Ready
+            } else {
+                StationState::Disabled(String::from("Nothing hot to cool"))
+            }
+        }
+        StationKind::Grindstone => {
+            if inventory.iter().any(|x| x.due(StepKind::Grind)) {
+                StationState::Ready
+            } else {
+                StationState::Disabled(String::from("Nothing ready to grind"))
+            }
+        }
+        StationKind::Rack => {
+            let on_rack = count_at(game, Location::Rack);
+            if on_rack == 0 && count_at(game, Location::Storage) == 0 {
+                StationState::Disabled(String::from("Nothing to put out"))
+            } else if on_rack >= RACK_SPACE {
+                StationState::Note(format!("Rack full: {}/{}", on_rack, RACK_SPACE))
+            } else {
+                StationState::Note(format!("On the rack: {}/{}", on_rack, RACK_SPACE))
+            }
+        }
+        StationKind::Chest => {
+            if inventory.is_empty() {
+                StationState::Disabled(String::from("Chest is empty"))
+            } else {
+                StationState::Ready
+            }
+        }
+        StationKind::Desk => {
+            if shop_open(game.state.day, game.state.time) {
+                StationState::Ready
+            } else {
+                StationState::Note(String::from("Shop is closed to customers"))
+            }
+        }
+    }
+}
+
+// Use whichever station the player is at
+pub fn use_station(game: &mut Game, kind: StationKind) {
+    if let StationState::Disabled(reason) = station_state(game, kind) {
+        display_error(game, &reason);
+        return;
+    }
+
+    match kind {
+        StationKind::Anvil => {
+            // Run anvil minigame
+            if run_anvil(game) {
+                game.state.spend_time(Activity::Hammer);
+            }
+        }
+        // Inventory + workshop expansions
+        StationKind::Desk => use_desk(game),
+        // Put things in or take them out
+        StationKind::Forge => use_forge(game),
+        StationKind::QuenchTub => use_quench_tub(game),
+        StationKind::Grindstone => use_grindstone(game),
+        StationKind::Rack => use_rack(game),
+        StationKind::Chest => {
+            display_inventory(game, Some(InventoryMode::View));
+        }
+    }
+}
+
+// Desk: inventory, shop and ledger, assembly, upgrades and expansions
+pub fn use_desk(game: &mut Game) {
+    let options = vec![
+        String::from("Inventory"),
+        String::from("Shop"),
+        String::from("Ledger"),
+        String::from("Bank"),
+        String::from("Orders"),
+        String::from("Assemble"),
+        String::from("Upgrades"),
+        String::from("Expand workshop"),
+        String::from("Settings"),
+        String::from("Sleep"),
+    ];
+
+    match pick_option(game, &options) {
+        Some(0) => {
+            display_inventory(game, None);
+        }
+        Some(1) => shop_screen(game),
+        Some(2) => display_ledger(game),
+        Some(3) => use_bank(game),
+        Some(4) => use_orders(game),
+        Some(5) => assemble(game),
+        Some(6) => upgrade_shop(game),
+        Some(7) => expand_workshop(game),
+        Some(8) => settings_screen(game),
+        Some(9) => sleep(game),
+        _ => (),
+    }
+}
+
+// Go to bed early, ending the day. Rack customers still come in the
+// hours slept through
+pub fn sleep(game: &mut Game) {
+    game.state.time = game.state.time.max(DAY_END);
+}
+
+// Hand a finished item over to a waiting customer
+pub fn use_orders(game: &mut Game) {
+    if game.state.orders.is_empty() {
+        display_error(game, "No one is waiting on an order");
+        return;
+    }
+
+    let options: Vec<String> = game
+        .state
+        .orders
+        .iter()
+        .map(|o| format!("{}: {}$, due Day {}", o, o.reward, o.due + 1))
+        .collect();
+    let order = match pick_option(game, &options) {
+        Some(i) => i,
+        None => return,
+    };
+
+    let item = match display_inventory(game, Some(InventoryMode::Select)) {
+        Some(i) => i,
+        None => return,
+    };
+
+    let p = &game.state.inventory[item];
+    if p.location != Location::Storage && p.location != Location::Rack {
+        display_error(game, "That item is busy");
+        return;
+    }
+    if let Some(reason) = game.state.orders[order].refusal(p) {
+        display_error(game, &reason);
+        return;
+    }
+
+    let reward = game.state.orders[order].reward;
+    game.state.deliver(order, item);
+    game.state.spend_time(Activity::Shop);
+    notify(
+        game,
+        &format!("Order delivered: {}$", reward),
+        Severity::Success,
+    );
+}
+
+// Borrow money or pay it back until the player backs out
+pub fn use_bank(game: &mut Game) {
+    loop {
+        let state = &game.state;
+        let can_borrow = (LOAN_LIMIT - state.debt).min(LOAN_STEP);
+        let can_repay = state.debt.min(LOAN_STEP).min(state.money);
+
+        let options = vec![
+            format!("Owed: {}$ ({}% a day)", state.debt, INTEREST),
+            format!("Borrow {}$", can_borrow),
+            format!("Repay {}$", can_repay),
+        ];
+
+        match pick_option(game, &options) {
+            Some(1) => {
+                if can_borrow <= 0 {
+                    display_error(game, "The bank won't lend more");
+                    continue;
+                }
+                game.state.borrow(can_borrow);
+            }
+            Some(2) => {
+                if can_repay <= 0 {
+                    display_error(game, "Nothing to repay");
+                    continue;
+                }
+                game.state.repay(can_repay);
+            }
+            Some(_) => (),
+            None => return,
+        }
+    }
+}
+
+// Buy tiered upgrades until the player backs out
+pub fn upgrade_shop(game: &mut Game) {
+    let kinds = UpgradeKind::all();
+
+    loop {
+        let upgrades = &game.state.upgrades;
+        let options: Vec<String> = kinds
+            .iter()
+            .map(|k| {
+                let next = upgrades.tier(*k) + 1;
+                if next > k.max_tier() {
+                    return format!("{} (max)", k);
+                }
+                match k.requirement(next) {
+                    Some(r) if !upgrades.meets(&r) => {
+                        format!("{} {}: needs {}", k, next, r)
+                    }
+                    _ => format!("{} {}: {}$", k, next, k.price(next)),
+                }
+            })
+            .collect();
+
+        let k = match pick_option(game, &options) {
+            Some(i) => kinds[i],
+            None => return,
+        };
+
+        let next = game.state.upgrades.tier(k) + 1;
+        if next > k.max_tier() {
+            display_error(game, "Already fully upgraded");
+            continue;
+        }
+        if let Some(r) = k.requirement(next) {
+            if !game.state.upgrades.meets(&r) {
+                display_error(game, &format!("Needs {}", r));
+                continue;
+            }
+        }
+        if game.state.money < k.price(next) {
+            display_error(game, "Not enough money");
+            continue;
+        }
+
+        game.state.transact(
+            TransactionKind::Purchase,
+            format!("{} {}", k, next),
+            -k.price(next),
+        );
+        game.state.upgrades.buy(k);
+        game.state.spend_time(Activity::Shop);
+    }
+}
+
+// Buy a new station for the workshop
+pub fn expand_workshop(game: &mut Game) {
+    let available: Vec<Expansion> = Expansion::all()
+        .into_iter()
+        .filter(|e| !game.state.upgrades.has(*e))
+        .collect();
+
+    if available.is_empty() {
+        display_error(game, "Workshop is fully built");
+        return;
+    }
+
+    let options: Vec<String> = available
+        .iter()
+        .map(|e| format!("{}: {}$", e, e.price()))
+        .collect();
+
+    let e = match pick_option(game, &options) {
+        Some(i) => available[i],
+        None => return,
+    };
+
+    if game.state.money < e.price() {
+        display_error(game, "Not enough money");
+        return;
+    }
+
+    game.state
+        .transact(TransactionKind::Purchase, e.to_string(), -e.price());
+    game.state.upgrades.build(e);
+    game.state.spend_time(Activity::Shop);
+}
+
+// Forge: put items in to heat, or take them out
+pub fn use_forge(game: &mut Game) {
+    let in_forge = game
+        .state
+        .inventory
+        .iter()
+        .any(|x| x.location == Location::Forge);
+
+    let mut actions: Vec<StationAction> = vec![(String::from("Put item in"), move_to_furnace)];
+    if in_forge {
+        actions.push((String::from("Take item out"), take_from_forge));
+    }
+    actions.push((String::from("Charge crucible"), charge_crucible));
+    actions.push((String::from("Melt down item"), melt_down));
+    actions.push((String::from("Combine scrap"), combine_scrap));
+
+    let options: Vec<String> = actions.iter().map(|x| x.0.clone()).collect();
+    if let Some(i) = pick_option(game, &options) {
+        (actions[i].1)(game);
+    }
+}
+
+// Fill a crucible with ore and put it in the forge to smelt
+pub fn charge_crucible(game: &mut Game) {
+    if forge_full(game) {
+        display_error(game, "Not enough furnace space");
+        return;
+    }
+
+    let ores = Ore::all();
+    let mut charge: Vec<Ore> = Vec::new();
+
+    // Add ore a unit at a time until it's full or the player smelts it
+    loop {
+        let mut options: Vec<String> = ores
+            .iter()
+            .map(|o| {
+                let have = game.state.ores.iter().filter(|x| *x == o).count();
+                let used = charge.iter().filter(|x| *x == o).count();
+                format!("{}: {} in (have {})", o, used, have - used)
+            })
+            .collect();
+        options.push(format!("Smelt ({}/{})", charge.len(), CRUCIBLE_SIZE));
+
+        let i = match pick_option(game, &options) {
+            Some(i) => i,
+            None => return,
+        };
+
+        if i < ores.len() {
+            let have = game.state.ores.iter().filter(|x| **x == ores[i]).count();
+            let used = charge.iter().filter(|x| **x == ores[i]).count();
+            if used >= have {
+                display_error(game, &format!("Out of {}", ores[i]));
+            } else if charge.len() >= CRUCIBLE_SIZE {
+                display_error(game, "Crucible is full");
+            } else {
+                charge.push(ores[i]);
+            }
+            continue;
+        }
+
+        if charge.len() < CRUCIBLE_SIZE {
+            display_error(game, "Fill the crucible first");
+            continue;
+        }
+
+        match smelt(&charge) {
+            Ok((material, purity)) => {
+                // Use up the ore
+                for o in &charge {
+                    let j = game.state.ores.iter().position(|x| x == o).unwrap();
+                    game.state.ores.remove(j);
+                }
+
+                let mut p = Product::new(material);
+                p.form = Form::Crucible;
+                p.location = Location::Forge;
+                p.purity = purity;
+                p.history.push(Step::Heat);
+                p.appraise();
+                game.state.inventory.push(p);
+                game.state.spend_time(Activity::Forge);
+                return;
+            }
+            Err(e) => display_error(game, &e),
+        }
+    }
+}
+
+// Whether every forge slot is taken
+fn forge_full(game: &Game) -> bool {
+    let num_items = game
+        .state
+        .inventory
+        .iter()
+        .filter(|x| x.location == Location::Forge)
+        .count() as i32;
+
+    num_items >= game.state.upgrades.forge_space
+}
+
+pub fn move_to_furnace(game: &mut Game) {
+    // Again probably add some error handling in the future
+    if forge_full(game) {
+        display_error(game, "Not enough furnace space");
+        return;
+    }
+    // Pick what to move
+    let ret = display_inventory(game, Some(InventoryMode::Select));
+
+    // Move it, only from storage
+    if let Some(i) = ret {
+        match game.state.inventory[i].location {
+            Location::Storage => (),
+            Location::Forge => {
+                display_error(game, "It's already in the forge");
+                return;
+            }
+            Location::Rack => {
+                display_error(game, "Take it off the rack first");
+                return;
+            }
+            _ => {
+                display_error(game, "That item is busy");
+                return;
+            }
+        }
+        game.state.inventory[i].location = Location::Forge;
+        game.state.inventory[i].history.push(Step::Heat);
+        game.state.spend_time(Activity::Forge);
+    }
+}
+
+// Whether the item is due for the given step, telling the player if not
+fn due_for(game: &mut Game, i: usize, kind: StepKind) -> bool {
+    let item = &game.state.inventory[i];
+    match item.form {
+        Form::Bar => {
+            display_error(game, "Shape it on the anvil first");
+            return false;
+        }
+        Form::Crucible | Form::Scrap => {
+            display_error(game, "Smelt it into a bar first");
+            return false;
+        }
+        _ => (),
+    }
+
+    if item.due(kind) {
+        return true;
+    }
+    match item.next_step() {
+        Some(next) => {
+            display_error(game, &format!("Next step: {}", next));
+            false
+        }
+        None => {
+            display_error(game, "Item is already finished");
+            false
+        }
+    }
+}
+
+// Put a botched or unwanted item back in a crucible, losing some metal
+pub fn melt_down(game: &mut Game) {
+    if forge_full(game) {
+        display_error(game, "Not enough furnace space");
+        return;
+    }
+
+    let i = match display_inventory(game, Some(InventoryMode::Select)) {
+        Some(i) => i,
+        None => return,
+    };
+
+    let item = game.state.inventory[i].clone();
+    match item.form {
+        Form::Bar | Form::Crucible => {
+            display_error(game, "That's already raw metal");
+            return;
+        }
+        Form::Scrap => {
+            display_error(game, "Use Combine scrap to melt scrap down");
+            return;
+        }
+        _ => (),
+    }
+    if item.location != Location::Storage {
+        display_error(game, "Item is busy");
+        return;
+    }
+
+    // A quarter of the metal is lost as slag, fittings burn away
+    let mut p = Product::new(item.material);
+    p.form = Form::Crucible;
+    p.location = Location::Forge;
+    p.temp = item.temp;
+    p.purity = item.purity;
+    p.weight = (item.weight * 3 / 4).max(1);
+    p.history.push(Step::Heat);
+    p.appraise();
+
+    game.state.inventory[i] = p;
+    game.state.spend_time(Activity::Forge);
+}
+
+// Gather scrap of one metal into a crucible until there's a bar's worth
+pub fn combine_scrap(game: &mut Game) {
+    if forge_full(game) {
+        display_error(game, "Not enough furnace space");
+        return;
+    }
+
+    let mut picked: Vec<usize> = Vec::new();
+    let mut weight = 0;
+
+    while weight < BAR_WEIGHT {
+        let i = match display_inventory(game, Some(InventoryMode::Select)) {
+            Some(i) => i,
+            None => return,
+        };
+
+        let item = game.state.inventory[i].clone();
+        let first = picked.first().map(|x| game.state.inventory[*x].material);
+        if picked.contains(&i) {
+            display_error(game, "Already picked");
+        } else if !matches!(item.form, Form::Scrap) || item.location != Location::Storage {
+            display_error(game, "Pick scrap from storage");
+        } else if first.is_some_and(|m| m != item.material) {
+            display_error(game, "Scrap has to be the same metal");
+        } else {
+            picked.push(i);
+            weight += item.weight;
+            notify(
+                game,
+                &format!("Scrap: {}/{}", weight, BAR_WEIGHT),
+                Severity::Info,
+            );
+        }
+    }
+
+    // Purity ends up as the average by weight
+    let material = game.state.inventory[picked[0]].material;
+    let purity = picked
+        .iter()
+        .map(|x| game.state.inventory[*x].purity * game.state.inventory[*x].weight)
+        .sum::<i32>()
+        / weight;
+
+    // Remove from the back so indices stay valid
+    picked.sort();
+    for i in picked.iter().rev() {
+        game.state.inventory.remove(*i);
+    }
+
+    let mut p = Product::new(material);
+    p.form = Form::Crucible;
+    p.location = Location::Forge;
+    p.purity = purity;
+    p.weight = weight;
+    p.history.push(Step::Heat);
+    p.appraise();
+    game.state.inventory.push(p);
+    game.state.spend_time(Activity::Forge);
+}
+
+// Pull an item out of the forge, tempering it if it was hardened
+pub fn take_from_forge(game: &mut Game) {
+    let i = match display_inventory(game, Some(InventoryMode::Select)) {
+        Some(i) => i,
+        None => return,
+    };
+
+    let item = game.state.inventory[i].clone();
+    if item.location != Location::Forge {
+        display_error(game, "Item isn't in the forge");
+        return;
+    }
+
+    // Crucibles stay in until they've melted
+    if let Form::Crucible = item.form {
+        if let Temp::Under = item.temp_val() {
+            display_error(game, "Not hot enough to smelt yet");
+            return;
+        }
+
+        game.state.spend_time(Activity::Forge);
+
+        // A bar's worth or more pours into a bar, anything short is scrap
+        let p = &mut game.state.inventory[i];
+        p.location = Location::Storage;
+        p.temp = 70;
+        if p.weight >= BAR_WEIGHT {
+            p.form = Form::Bar;
+        } else {
+            p.form = Form::Scrap;
+        }
+        p.appraise();
+
+        let message = format!("Smelted {} (purity {})", item.material, item.purity);
+        notify(game, &message, Severity::Success);
+        return;
+    }
+
+    game.state.inventory[i].location = Location::Storage;
+    game.state.spend_time(Activity::Forge);
+
+    if item.next_step() != Some(StepKind::Temper) {
+        return;
+    }
+
+    // Tempering trades a little hardness for toughness
+    let p = &mut game.state.inventory[i];
+    match item.temp_val() {
+        Temp::Under => display_error(game, "Too cool to temper"),
+        Temp::Perfect => {
+            p.history.push(Step::Temper(true));
+            p.appraise();
+            notify(game, "Tempered", Severity::Success);
+        }
+        Temp::Over => {
+            p.hardness /= 2;
+            p.history.push(Step::Temper(false));
+            p.appraise();
+            notify(game, "Overtempered, it went soft", Severity::Warning);
+        }
+    }
+}
+
+// Quench tub: harden a freshly forged item, or just cool an item down
+pub fn use_quench_tub(game: &mut Game) {
+    let i = match display_inventory(game, Some(InventoryMode::Select)) {
+        Some(i) => i,
+        None => return,
+    };
+
+    let item = game.state.inventory[i].clone();
+    if let Form::Crucible | Form::Scrap = item.form {
+        display_error(game, "Smelt it into a bar first");
+        return;
+    }
+    // Straight from the forge, or hot off the anvil
+    if item.location != Location::Forge && item.location != Location::Storage {
+        display_error(game, "That item is busy");
+        return;
+    }
+    if item.temp <= 70 {
+        display_error(game, "Item is already cold");
+        return;
+    }
+
+    if item.next_step() == Some(StepKind::Quench) {
+        run_quench(game, i);
+        return;
+    }
+
+    game.state.inventory[i].temp = 70;
+    game.state.inventory[i].location = Location::Storage;
+    game.state.spend_time(Activity::Quench);
+}
+
+// Grindstone: sharpen and polish a finished item
+pub fn use_grindstone(game: &mut Game) {
+    let i = match display_inventory(game, Some(InventoryMode::Select)) {
+        Some(i) => i,
+        None => return,
+    };
+
+    if due_for(game, i, StepKind::Grind) {
+        run_grind(game, i);
+    }
+}
+
+// Desk: fit the handle or hilt to a finished blade or head
+pub fn assemble(game: &mut Game) {
+    let i = match display_inventory(game, Some(InventoryMode::Select)) {
+        Some(i) => i,
+        None => return,
+    };
+
+    if !due_for(game, i, StepKind::Assemble) {
+        return;
+    }
+
+    // Every required part has to be in stock
+    let form = game.state.inventory[i].form;
+    let mut stock = game.state.parts.clone();
+    for part in form.parts() {
+        match stock.iter().position(|x| *x == part) {
+            Some(j) => {
+                stock.remove(j);
+            }
+            None => {
+                display_error(game, &format!("Needs a {}", part));
+                return;
+            }
+        }
+    }
+    let mut fitted = form.parts();
+
+    // Offer any extras that are in stock
+    for part in form.optional_parts() {
+        if let Some(j) = stock.iter().position(|x| *x == part) {
+            let options = vec![
+                format!("Add {} (+{}$)", part, part.value()),
+                String::from("Leave it off"),
+            ];
+            if let Some(0) = pick_option(game, &options) {
+                stock.remove(j);
+                fitted.push(part);
+            }
+        }
+    }
+
+    game.state.parts = stock;
+    let p = &mut game.state.inventory[i];
+    p.parts = fitted;
+    p.history.push(Step::Assemble);
+    p.appraise();
+
+    if p.is_finished() {
+        game.state.today.crafted += 1;
+    }
+    if p.is_masterpiece() {
+        game.state.masterpieces += 1;
+        game.state.reputation += MASTERPIECE_REPUTATION;
+        notify(game, "A masterpiece! Word gets around", Severity::Success);
+    }
+    game.state.spend_time(Activity::Assemble);
+}
+
+// Display rack: put items out for sale or take them back
+pub fn use_rack(game: &mut Game) {
+    let i = match display_inventory(game, Some(InventoryMode::Select)) {
+        Some(i) => i,
+        None => return,
+    };
+
+    let on_rack = game
+        .state
+        .inventory
+        .iter()
+        .filter(|x| x.location == Location::Rack)
+        .count();
+
+    match game.state.inventory[i].location {
+        Location::Rack => game.state.inventory[i].location = Location::Storage,
+        Location::Storage => {
+            if on_rack >= RACK_SPACE {
+                display_error(game, "Display rack is full");
+                return;
+            }
+            game.state.inventory[i].location = Location::Rack;
+        }
+        _ => {
+            display_error(game, "Item is busy");
+            return;
+        }
+    }
+    game.state.spend_time(Activity::Rack);
+}
+
+// An hour of customers looking over the rack, they may buy at a markup.
+// Returns money made
+pub fn sell_from_rack(game: &mut Game) -> i32 {
+    let mut rng = rand::thread_rng();
+    let mut made = 0;
+
+    let mut sold: Vec<(String, i32, i32)> = Vec::new();
+
+    game.state.inventory.retain(|x| {
+        if x.location == Location::Rack && rng.gen_range(0..12) == 0 {
+            sold.push((x.name(), x.value + x.value / 4, x.reputation()));
+            return false;
+        }
+        true
+    });
+
+    for (item, price, reputation) in sold {
+        made += price;
+        game.state
+            .transact(TransactionKind::Sale, format!("Rack: {}", item), price);
+        game.state.reputation += reputation;
+    }
+    made
+}
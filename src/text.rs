@@ -0,0 +1,428 @@
+use super::ui::*;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::ttf::Font;
+use sdl2::video::Window;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+// A run of text in one colour
+#[derive(Clone)]
+pub struct Span {
+    pub text: String,
+    pub color: Color,
+}
+
+impl Span {
+    pub fn new(text: &str, color: Color) -> Span {
+        Span {
+            text: String::from(text),
+            color,
+        }
+    }
+}
+
+// One line of laid out text, still split by colour
+pub type Line = Vec<Span>;
+
+// How a block of text is fitted into its rect
+#[derive(Clone, Copy)]
+pub struct TextStyle {
+    pub align: Align,
+    // Centre the block top to bottom as well
+    pub middle: bool,
+    // Extra pixels between lines
+    pub line_spacing: i32,
+    // Break onto new lines to fit the width, otherwise stay on one line
+    pub wrap: bool,
+    // End text that doesn't fit with "...", otherwise let it spill over
+    pub ellipsis: bool,
+}
+
+impl TextStyle {
+    // Wrapped, left aligned from the top
+    pub fn new() -> TextStyle {
+        TextStyle {
+            align: Align::Left,
+            middle: false,
+            line_spacing: 4,
+            wrap: true,
+            ellipsis: true,
+        }
+    }
+
+    // One line cut short to fit, the way list rows and buttons want it
+    pub fn single_line(align: Align) -> TextStyle {
+        TextStyle {
+            align,
+            middle: true,
+            wrap: false,
+            ..TextStyle::new()
+        }
+    }
+
+    // Wrapped and centered both ways, for popups and titles
+    pub fn centered() -> TextStyle {
+        TextStyle {
+            align: Align::Center,
+            middle: true,
+            ..TextStyle::new()
+        }
+    }
+}
+
+pub fn text_width(font: &Font, s: &str) -> i32 {
+    if s.is_empty() {
+        return 0;
+    }
+    font.size_of(s).unwrap().0 as i32
+}
+
+// Width of a string in pixels. Drawing passes text_width with its font,
+// anything else can lay text out without one
+pub type Measure<'a> = &'a dyn Fn(&str) -> i32;
+
+pub fn line_width(measure: Measure, line: &Line) -> i32 {
+    line.iter().map(|s| measure(&s.text)).sum()
+}
+
+// Where a line starts in a rect at x, w wide
+pub fn align_x(align: Align, x: i32, w: i32, line_w: i32) -> i32 {
+    match align {
+        Align::Left => x,
+        Align::Center => x + (w - line_w) / 2,
+        Align::Right => x + w - line_w,
+    }
+}
+
+// Add text to the end of a line, joining it onto the last span if the colour matches
+fn push_text(line: &mut Line, text: &str, color: Color) {
+    match line.last_mut() {
+        Some(last) if last.color == color => last.text.push_str(text),
+        _ => line.push(Span::new(text, color)),
+    }
+}
+
+// A word and the colour of the space before it. A word can carry on
+// across spans, so it's kept in coloured pieces
+struct Word {
+    pieces: Line,
+    space: Color,
+}
+
+// Split spans at spaces into words, None where there's a newline
+fn words(spans: &[Span]) -> Vec<Option<Word>> {
+    let first = match spans.first() {
+        Some(s) => s.color,
+        None => return vec![],
+    };
+    let mut words = vec![Some(Word {
+        pieces: vec![],
+        space: first,
+    })];
+
+    for span in spans {
+        for (i, para) in span.text.split('\n').enumerate() {
+            if i > 0 {
+                words.push(None);
+                words.push(Some(Word {
+                    pieces: vec![],
+                    space: span.color,
+                }));
+            }
+            for (j, word) in para.split(' ').enumerate() {
+                // Text before the first space carries on the last word
+                if j > 0 {
+                    words.push(Some(Word {
+                        pieces: vec![],
+                        space: span.color,
+                    }));
+                }
+                if let Some(Some(w)) = words.last_mut() {
+                    if !word.is_empty() {
+                        push_text(&mut w.pieces, word, span.color);
+                    }
+                }
+            }
+        }
+    }
+    words
+}
+
+// Break text into lines no wider than width. Words are never split, even
+// where they change colour, so a single word wider than the line gets a
+// line to itself
+pub fn wrap(measure: Measure, spans: &[Span], width: i32) -> Vec<Line> {
+    let mut lines: Vec<Line> = vec![Vec::new()];
+    let mut w = 0;
+    // Nothing placed on the line yet, so the next word needs no space
+    let mut fresh = true;
+
+    for word in words(spans) {
+        let word = match word {
+            Some(word) => word,
+            None => {
+                lines.push(Vec::new());
+                w = 0;
+                fresh = true;
+                continue;
+            }
+        };
+        let word_w = line_width(measure, &word.pieces);
+        let line = lines.last_mut().unwrap();
+
+        if fresh {
+            fresh = false;
+            w = word_w;
+            for p in &word.pieces {
+                push_text(line, &p.text, p.color);
+            }
+            continue;
+        }
+
+        let space_w = measure(" ");
+        if w + space_w + word_w > width {
+            // The space at the break is dropped, and with it any run of
+            // spaces that would start the next line
+            if word.pieces.is_empty() {
+                continue;
+            }
+            lines.push(word.pieces);
+            w = word_w;
+        } else {
+            push_text(line, " ", word.space);
+            for p in &word.pieces {
+                push_text(line, &p.text, p.color);
+            }
+            w += space_w + word_w;
+        }
+    }
+
+    lines
+}
+
+// Cut characters off the end of a line until it fits with "..." after it
+pub fn truncate(measure: Measure, line: &Line, width: i32) -> Line {
+    if line_width(measure, line) <= width {
+        return line.clone();
+    }
+
+    let mut out = line.clone();
+    let dots_color = match out.last() {
+        Some(s) => s.color,
+        None => return out,
+    };
+    loop {
+        // Drop empty spans, then a character from the last one
+        while let Some(last) = out.last() {
+            if last.text.is_empty() {
+                out.pop();
+            } else {
+                break;
+            }
+        }
+        match out.last_mut() {
+            Some(last) => {
+                last.text.pop();
+            }
+            None => break,
+        }
+
+        let mut with_dots = out.clone();
+        push_text(&mut with_dots, "...", dots_color);
+        if line_width(measure, &with_dots) <= width {
+            return with_dots;
+        }
+    }
+
+    vec![Span::new("...", dots_color)]
+}
+
+// Lines to draw for some text in a rect of the given size, with lines
+// line_h tall
+pub fn layout(
+    measure: Measure,
+    line_h: i32,
+    spans: &[Span],
+    w: i32,
+    h: i32,
+    style: &TextStyle,
+) -> Vec<Line> {
+    let mut lines = if style.wrap {
+        wrap(measure, spans, w)
+    } else {
+        // Newlines are only kept when wrapping
+        let mut line = Vec::new();
+        for s in spans {
+            push_text(&mut line, &s.text.replace('\n', " "), s.color);
+        }
+        vec![line]
+    };
+
+    if !style.ellipsis {
+        return lines;
+    }
+
+    // Lines past the bottom are dropped, with "..." on the last one shown
+    let line_h = line_h + style.line_spacing;
+    let fit = ((h + style.line_spacing) / line_h).max(1) as usize;
+    if lines.len() > fit {
+        lines.truncate(fit);
+        let last = lines.last_mut().unwrap();
+        let color = match last.last() {
+            Some(s) => s.color,
+            None => Color::RGB(255, 255, 255),
+        };
+        let mut cut = last.clone();
+        push_text(&mut cut, " ...", color);
+        *last = truncate(measure, &cut, w);
+    }
+
+    lines.iter().map(|l| truncate(measure, l, w)).collect()
+}
+
+// Draw text into a rect, returning how tall it came out
+pub fn draw_spans(
+    canvas: &mut Canvas<Window>,
+    font: &mut Font,
+    spans: &[Span],
+    rect: Rect,
+    style: &TextStyle,
+) -> i32 {
+    let w = rect.width() as i32;
+    let h = rect.height() as i32;
+    let measure = |s: &str| text_width(font, s);
+    let lines = layout(&measure, font.height(), spans, w, h, style);
+    let widths: Vec<i32> = lines.iter().map(|l| line_width(&measure, l)).collect();
+
+    let line_h = font.height();
+    let total = lines.len() as i32 * (line_h + style.line_spacing) - style.line_spacing;
+
+    let mut y = rect.y;
+    if style.middle {
+        y += (h - total) / 2;
+    }
+
+    let tc = canvas.texture_creator();
+    for (line, lw) in lines.iter().zip(widths) {
+        let mut x = align_x(style.align, rect.x, w, lw);
+
+        for span in line {
+            // Fonts can't render empty strings
+            if span.text.is_empty() {
+                continue;
+            }
+            let t = create_text(&span.text, &tc, font, span.color);
+            let (tw, th) = (t.query().width, t.query().height);
+            canvas
+                .copy(&t, None, Some(Rect::new(x, y, tw, th)))
+                .unwrap();
+            x += tw as i32;
+        }
+
+        y += line_h + style.line_spacing;
+    }
+
+    total
+}
+
+// Plain text in a single colour
+pub fn draw_string(
+    canvas: &mut Canvas<Window>,
+    font: &mut Font,
+    s: &str,
+    color: Color,
+    rect: Rect,
+    style: &TextStyle,
+) -> i32 {
+    draw_spans(canvas, font, &[Span::new(s, color)], rect, style)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Color = Color::RGB(255, 0, 0);
+    const BLUE: Color = Color::RGB(0, 0, 255);
+
+    // Every character is 10 pixels wide
+    fn measure(s: &str) -> i32 {
+        s.chars().count() as i32 * 10
+    }
+
+    fn texts(lines: &[Line]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|l| l.iter().map(|s| s.text.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn overlong_first_word_gets_its_own_line() {
+        let spans = [Span::new("abcdefghijkl rest", RED)];
+        let lines = wrap(&measure, &spans, 50);
+        assert_eq!(texts(&lines), ["abcdefghijkl", "rest"]);
+
+        // Also when it's the first word of a span after other text
+        let spans = [Span::new("ab ", RED), Span::new("cdefghij", BLUE)];
+        let lines = wrap(&measure, &spans, 50);
+        assert_eq!(texts(&lines), ["ab", "cdefghij"]);
+        assert!(lines[1][0].color == BLUE);
+    }
+
+    #[test]
+    fn words_across_spans_stay_together() {
+        let spans = [Span::new("a hel", RED), Span::new("lo world", BLUE)];
+        let lines = wrap(&measure, &spans, 60);
+        assert_eq!(texts(&lines), ["a", "hello", "world"]);
+        assert!(lines[1][0].color == RED && lines[1][0].text == "hel");
+        assert!(lines[1][1].color == BLUE && lines[1][1].text == "lo");
+    }
+
+    #[test]
+    fn newlines_and_fitting_text() {
+        let spans = [Span::new("one two\nthree", RED)];
+        assert_eq!(texts(&wrap(&measure, &spans, 200)), ["one two", "three"]);
+    }
+
+    #[test]
+    fn aligns_within_the_rect() {
+        assert_eq!(align_x(Align::Left, 10, 100, 40), 10);
+        assert_eq!(align_x(Align::Center, 10, 100, 40), 40);
+        assert_eq!(align_x(Align::Right, 10, 100, 40), 70);
+    }
+
+    #[test]
+    fn truncates_with_dots() {
+        let line = vec![Span::new("hello world", RED)];
+        assert_eq!(texts(&[truncate(&measure, &line, 60)]), ["hel..."]);
+        assert_eq!(texts(&[truncate(&measure, &line, 200)]), ["hello world"]);
+    }
+
+    #[test]
+    fn ellipsis_once_the_height_runs_out() {
+        let spans = [Span::new("aaa bbb ccc", RED)];
+        let style = TextStyle::new();
+
+        // Two 10 pixel lines with 4 between them fit in 24
+        let lines = layout(&measure, 10, &spans, 50, 24, &style);
+        assert_eq!(texts(&lines), ["aaa", "bb..."]);
+
+        let lines = layout(&measure, 10, &spans, 50, 38, &style);
+        assert_eq!(texts(&lines), ["aaa", "bbb", "ccc"]);
+
+        let style = TextStyle {
+            ellipsis: false,
+            ..TextStyle::new()
+        };
+        let lines = layout(&measure, 10, &spans, 50, 24, &style);
+        assert_eq!(lines.len(), 3);
+    }
+}
@@ -0,0 +1,164 @@
+use super::game::*;
+use super::text::*;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::ttf::{Font, Sdl2TtfContext};
+use sdl2::video::Window;
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// How long a toast stays up, the last part of it fading out
+const TOAST_TIME: Duration = Duration::from_millis(3000);
+const FADE_TIME: Duration = Duration::from_millis(500);
+
+// Toasts on screen at once, the rest wait their turn
+const MAX_SHOWN: usize = 4;
+
+const TOAST_WIDTH: u32 = 280;
+const TOAST_HEIGHT: u32 = 50;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    // Colour of the stripe down the side of the toast
+    pub fn color(&self) -> Color {
+        match self {
+            Severity::Info => Color::RGB(80, 160, 235),
+            Severity::Success => Color::RGB(119, 235, 52),
+            Severity::Warning => Color::RGB(235, 204, 52),
+            Severity::Error => Color::RGB(235, 52, 52),
+        }
+    }
+}
+
+pub struct Toast {
+    pub message: String,
+    pub severity: Severity,
+    // When it went up, None while it's still queued
+    pub shown_at: Option<Instant>,
+}
+
+impl Toast {
+    // 255 while showing, dropping to 0 as it fades out
+    fn alpha(&self, now: Instant) -> u8 {
+        let age = match self.shown_at {
+            Some(t) => now - t,
+            None => return 255,
+        };
+        if age + FADE_TIME < TOAST_TIME {
+            return 255;
+        }
+        let left = TOAST_TIME.saturating_sub(age);
+        (255 * left.as_millis() / FADE_TIME.as_millis()) as u8
+    }
+}
+
+// Messages shown over whatever screen is up, without stopping the game
+pub struct Toasts {
+    pub queue: VecDeque<Toast>,
+    // Loaded once, toasts are drawn on every frame of every screen
+    pub font: Font<'static, 'static>,
+}
+
+impl Toasts {
+    pub fn new(ttf: &'static Sdl2TtfContext) -> Toasts {
+        Toasts {
+            queue: VecDeque::new(),
+            font: ttf
+                .load_font("assets/SupermercadoOne-Regular.ttf", 16)
+                .unwrap(),
+        }
+    }
+
+    pub fn push(&mut self, message: &str, severity: Severity) {
+        // The same message again just stays up for longer
+        if let Some(last) = self.queue.back_mut() {
+            if last.message == message && last.severity == severity {
+                if last.shown_at.is_some() {
+                    last.shown_at = Some(Instant::now());
+                }
+                return;
+            }
+        }
+
+        self.queue.push_back(Toast {
+            message: String::from(message),
+            severity,
+            shown_at: None,
+        });
+    }
+
+    // Drop the ones that have timed out and put up the next in line
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        self.queue.retain(|t| match t.shown_at {
+            Some(shown) => now - shown < TOAST_TIME,
+            None => true,
+        });
+        for t in self.queue.iter_mut().take(MAX_SHOWN) {
+            if t.shown_at.is_none() {
+                t.shown_at = Some(now);
+            }
+        }
+    }
+
+    // Stacked up from the bottom right corner, oldest on top
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>) {
+        if self.queue.is_empty() {
+            return;
+        }
+
+        let now = Instant::now();
+        let shown: Vec<&Toast> = self.queue.iter().filter(|t| t.shown_at.is_some()).collect();
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        for (i, t) in shown.iter().enumerate() {
+            let a = t.alpha(now);
+            let from_bottom = (shown.len() - i) as i32;
+            let r = Rect::new(
+                (SCREEN_WIDTH - TOAST_WIDTH - 10) as i32,
+                SCREEN_HEIGHT as i32 - from_bottom * (TOAST_HEIGHT as i32 + 6) - 4,
+                TOAST_WIDTH,
+                TOAST_HEIGHT,
+            );
+
+            canvas.set_draw_color(Color::RGBA(20, 20, 20, a / 10 * 9));
+            canvas.fill_rect(r).unwrap();
+
+            let c = t.severity.color();
+            canvas.set_draw_color(Color::RGBA(c.r, c.g, c.b, a));
+            canvas
+                .fill_rect(Rect::new(r.x, r.y, 6, TOAST_HEIGHT))
+                .unwrap();
+
+            draw_string(
+                canvas,
+                &mut self.font,
+                &t.message,
+                Color::RGBA(255, 255, 255, a),
+                Rect::new(r.x + 14, r.y + 4, TOAST_WIDTH - 20, TOAST_HEIGHT - 8),
+                &TextStyle {
+                    middle: true,
+                    ..TextStyle::new()
+                },
+            );
+        }
+        canvas.set_blend_mode(BlendMode::None);
+    }
+
+    // Finish a frame: toasts go on top of everything else drawn
+    pub fn present(&mut self, canvas: &mut Canvas<Window>) {
+        self.update();
+        self.draw(canvas);
+        canvas.present();
+    }
+}
@@ -1,13 +1,14 @@
 use super::game::*;
+use super::input::*;
+use super::text::*;
+use super::toast::*;
+use super::widgets::*;
 
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
 use sdl2::render::Texture;
 use sdl2::render::TextureCreator;
-use sdl2::EventPump;
 
 use std::time::Duration;
 
@@ -18,8 +19,8 @@ pub enum HandlerRet {
 }
 
 pub enum InventoryMode {
-    BuySell,
     Select,
+    View,
 }
 
 pub fn create_text<'a, T>(
@@ -32,58 +33,111 @@ pub fn create_text<'a, T>(
     tc.create_texture_from_surface(&text_s).unwrap()
 }
 
-// Continue screen
-pub fn continue_screen(game: &mut Game, lines: Vec<&str>) {
-    // Load font
+// A title over some coloured lines, up until the player continues
+pub fn continue_screen(
+    game: &mut Game,
+    title: &str,
+    title_color: Color,
+    lines: &[(String, Color)],
+) {
+    // Load fonts
     let mut font = game
         .ttf
-        .load_font("assets/SupermercadoOne-Regular.ttf", 26)
+        .load_font("assets/SupermercadoOne-Regular.ttf", 40)
+        .unwrap();
+    let mut small_font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 24)
         .unwrap();
 
-    // Create tc
-    let tc = game.canvas.texture_creator();
-
-    // Generate textures
-    let mut texts = Vec::<Texture>::new();
+    let mut heading = Label::centered(title, Rect::new(0, 20, SCREEN_WIDTH, 80));
+    heading.color = title_color;
+    heading.set_text(title);
+
+    // One row per line, the bottom one has instructions
+    let mut labels: Vec<Label> = lines
+        .iter()
+        .enumerate()
+        .map(|(i, (line, color))| {
+            let mut l = Label::centered(line, Rect::new(0, 110 + 40 * i as i32, SCREEN_WIDTH, 40));
+            l.color = *color;
+            l.set_text(line);
+            l
+        })
+        .collect();
+    labels.push(Label::centered(
+        "Press ENTER to continue",
+        Rect::new(0, 400, SCREEN_WIDTH, 80),
+    ));
 
-    for line in lines {
-        texts.push(create_text(line, &tc, &mut font, Color::RGB(255, 255, 255)));
-    }
+    // UI loop
+    loop {
+        // Proceed when enter hit or clicked
+        for e in game.input.poll() {
+            match e {
+                InputEvent::Quit
+                | InputEvent::Press(Action::Interact)
+                | InputEvent::Press(Action::Back)
+                | InputEvent::Click(..) => return,
+                _ => (),
+            }
+        }
 
-    texts.push(create_text("Press ENTER to continue", &tc, &mut font, Color::RGB(255, 255, 255)));
+        // Background
+        game.canvas.set_draw_color(BACKGROUND);
+        game.canvas.clear();
 
-    // Split screen into 6 rects vertically, each the length of the screen
-    // Top four can hold lines, fifth blank, sixth has instructions
-    let mut rects = Vec::<Rect>::new();
+        // Draw lines
+        heading.draw(&mut game.canvas, &mut font);
+        for l in &labels {
+            l.draw(&mut game.canvas, &mut small_font);
+        }
 
-    for i in 0..(texts.len() - 1) {
-        let outer = Rect::new(0, 80 * (i as i32), 600, 80);
+        game.toasts.present(&mut game.canvas);
 
-        rects.push(center_text(outer, &texts[i]));
+        // Sleep
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
+}
 
-    rects.push(center_text(Rect::new(0, 400, 600, 80), &texts[texts.len() - 1]));
-
-    // UI loop
-    'scan: loop {
-        // Background
-        game.canvas.set_draw_color(Color::RGB(0, 0, 0));
-        game.canvas.clear();
-
-        // Proceed when enter hit
-        let mut dummy_active = -1;
-        match handle_selection(&mut game.event_pump, &mut dummy_active) {
-            HandlerRet::Accept => return,
-            _ => (),
-        };
+// White when cold, yellow when too hot, green when ready to work
+pub fn temp_color(t: &Temp) -> Color {
+    match t {
+        Temp::Under => Color::RGB(255, 255, 255),
+        Temp::Over => Color::RGB(235, 204, 52),
+        Temp::Perfect => Color::RGB(119, 235, 52),
+    }
+}
 
-        // Draw lines
-        for i in 0..texts.len() {
-            game.canvas.copy(&texts[i], None, Some(rects[i]));
-        }
+// Tint used when a material is named in text
+pub fn material_color(m: Material) -> Color {
+    match m {
+        Material::Iron => Color::RGB(170, 170, 180),
+        Material::Steel => Color::RGB(150, 190, 230),
+        Material::Bronze => Color::RGB(205, 127, 50),
+        Material::Silver => Color::RGB(225, 225, 235),
+        Material::Gold => Color::RGB(255, 215, 0),
+    }
+}
 
-        game.canvas.present();
+// "Steel Sword, 850°" with the metal and temperature coloured, then its work
+pub fn product_spans(p: &Product) -> Vec<Span> {
+    let grey = Color::RGB(200, 200, 200);
+    let mut spans = Vec::new();
+    if p.finish != Finish::Rough {
+        spans.push(Span::new(&format!("{} ", p.finish), grey));
     }
+    spans.push(Span::new(
+        &p.material.to_string(),
+        material_color(p.material),
+    ));
+    spans.push(Span::new(&format!(" {}, ", p.form), grey));
+    spans.push(Span::new(
+        &format!("{}°", p.temp),
+        temp_color(&p.temp_val()),
+    ));
+    spans.push(Span::new(&format!("\n{}", p.pipeline()), grey));
+    spans
 }
 
 // Center a texture in a larger rect
@@ -92,415 +146,201 @@ pub fn center_text(outer_rect: Rect, texture: &Texture) -> Rect {
     let w = texture.query().width;
     let h = texture.query().height;
 
-    // Find delta width + height
-    let dw = outer_rect.width() - texture.query().width;
-    let dh = outer_rect.height() - texture.query().height;
-
-    // Fail cases
-    if dw < 0 || dh < 0 {
-        panic!("Outer rect must be larger");
-    }
-
-    // Calculate padding offsets
-    let px: i32 = (dw / 2).try_into().unwrap();
-    let py: i32 = (dh / 2).try_into().unwrap();
+    // Text bigger than the rect spills out evenly on both sides
+    Rect::from_center(outer_rect.center(), w, h)
+}
 
-    // Return
-    Rect::new(outer_rect.x + px, outer_rect.y + py, w, h)
+// Which of a list of rows a point is over
+pub fn row_at(rows: &[Rect], x: i32, y: i32) -> Option<usize> {
+    rows.iter().position(|r| r.contains_point((x, y)))
 }
 
-pub fn handle_selection(event_pump: &mut EventPump, active: &mut i32) -> HandlerRet {
+// Move through a list of rows, returning when one is picked or the player
+// backs out. Screens with no rows continue on any click
+pub fn handle_selection(input: &mut Input, active: &mut i32, rows: &[Rect]) -> HandlerRet {
+    let len = rows.len() as i32;
+
     // Handle events
-    for e in event_pump.poll_iter() {
+    for e in input.poll() {
         match e {
-            Event::Quit { .. }
-            | Event::KeyDown {
-                keycode: Some(Keycode::Escape),
-                ..
-            } => return HandlerRet::Exit,
-            Event::KeyDown {
-                keycode: Some(Keycode::Up),
-                ..
-            } => {
-                if *active > -1 {
-                    *active -= 1;
+            InputEvent::Quit | InputEvent::Press(Action::Back) => return HandlerRet::Exit,
+            InputEvent::Press(Action::MoveUp) if *active > -1 => *active -= 1,
+            InputEvent::Press(Action::MoveDown) if *active + 1 < len => *active += 1,
+            InputEvent::Press(Action::Interact) => return HandlerRet::Accept,
+            InputEvent::MouseMove(x, y) => {
+                if let Some(i) = row_at(rows, x, y) {
+                    *active = i as i32;
                 }
             }
-            Event::KeyDown {
-                keycode: Some(Keycode::Down),
-                ..
-            } => {
-                if *active < 3 {
-                    *active += 1;
+            InputEvent::Click(x, y) => {
+                if rows.is_empty() {
+                    return HandlerRet::Accept;
+                }
+                if let Some(i) = row_at(rows, x, y) {
+                    *active = i as i32;
+                    return HandlerRet::Accept;
                 }
             }
-            Event::KeyDown {
-                keycode: Some(Keycode::Return),
-                ..
-            } => return HandlerRet::Accept,
+            InputEvent::Wheel(dy) if len > 0 => *active = (*active - dy).clamp(0, len - 1),
             _ => (),
         }
     }
-    return HandlerRet::Nothing;
+    HandlerRet::Nothing
 }
 
-// Display errors to user in pop-up window
-pub fn display_error(game: &mut Game, message: &str) {
-    // Load font
-    let mut font = game
-        .ttf
-        .load_font("assets/SupermercadoOne-Regular.ttf", 26)
-        .unwrap();
-
-    // Prep screen
-
-    // Backdrop
-    let backdrop = Rect::new(150, 120, 300, 240);
-
-    // Create tc
-    let tc = game.canvas.texture_creator();
-
-    // Create text
-    let text = create_text(message, &tc, &mut font, Color::RGB(255, 255, 255));
-
-    // Get text rect
-    let rect = center_text(backdrop, &text);
-
-    // Draw
-    game.canvas.set_draw_color(Color::RGB(255, 255, 255));
-    game.canvas.clear();
-
-    game.canvas.set_draw_color(Color::RGB(0, 0, 0));
-    game.canvas.fill_rect(backdrop).unwrap();
-
-    game.canvas.copy(&text, None, Some(rect));
-    game.canvas.present();
+// Show a message over the screen for a few seconds, without stopping the game
+pub fn notify(game: &mut Game, message: &str, severity: Severity) {
+    game.toasts.push(message, severity);
+}
 
-    // Sleep
-    std::thread::sleep(Duration::new(1, 0));
+// Tell the user why something couldn't be done
+pub fn display_error(game: &mut Game, message: &str) {
+    notify(game, message, Severity::Error);
 }
 
 // UI for picking a form
 pub fn pick_form(game: &mut Game) -> Option<Form> {
-    // Backdrop setup
-    let backdrop = Rect::new(200, 160, 200, 160);
-
     // Possible return values
-    let vals = vec![Form::Spear, Form::Axe, Form::Hammer, Form::Sword];
+    let vals = [Form::Spear, Form::Axe, Form::Hammer, Form::Sword];
+    let options = vec![
+        String::from("Spear (6u)"),
+        String::from("Axe (8u)"),
+        String::from("Hammer (10u)"),
+        String::from("Sword (12u)"),
+    ];
+
+    pick_option(game, &options).map(|i| vals[i])
+}
+
+// Most options shown at once before the list scrolls
+const MAX_OPTIONS_SHOWN: i32 = 8;
+
+// UI for picking one of a list of options
+pub fn pick_option(game: &mut Game, options: &[String]) -> Option<usize> {
+    // Backdrop grows with the number of options
+    let h = 40 * (options.len() as i32).min(MAX_OPTIONS_SHOWN);
+    let top = (SCREEN_HEIGHT as i32 - h) / 2;
+    let backdrop = Panel::new(Rect::new(100, top, 400, h as u32));
+
+    let mut list = List::new(backdrop.rect, 40);
+    list.centered = true;
+    list.set_items(options.iter().map(|o| (o.clone(), TEXT)).collect());
 
     // Load the font
     let mut font = game
         .ttf
-        .load_font("assets/SupermercadoOne-Regular.ttf", 26)
+        .load_font("assets/SupermercadoOne-Regular.ttf", 24)
         .unwrap();
 
-    // Create tc
-    let tc = game.canvas.texture_creator();
-
-    // Piece of text for each form
-    let mut texts = Vec::<Texture>::new();
-    texts.push(create_text(
-        "Spear",
-        &tc,
-        &mut font,
-        Color::RGB(255, 255, 255),
-    ));
-    texts.push(create_text(
-        "Axe",
-        &tc,
-        &mut font,
-        Color::RGB(255, 255, 255),
-    ));
-    texts.push(create_text(
-        "Hammer",
-        &tc,
-        &mut font,
-        Color::RGB(255, 255, 255),
-    ));
-    texts.push(create_text(
-        "Sword",
-        &tc,
-        &mut font,
-        Color::RGB(255, 255, 255),
-    ));
-
-    // Create rects
-    let mut rects = Vec::<Rect>::new();
-    for i in 0..4 {
-        let w = texts[i].query().width;
-        let h = texts[i].query().height;
-
-        let outer = Rect::new(200, 160 + (i as i32 * 40), 200, 40);
-
-        rects.push(center_text(outer, &texts[i]));
-    }
-
-    // Active selection
-    let mut active: i32 = -1;
-
-    // Loop
-    'go: loop {
+    loop {
         // Handle events
-        let e = handle_selection(&mut game.event_pump, &mut active);
-
-        match e {
-            HandlerRet::Exit => break 'go,
-            HandlerRet::Accept => {
-                if active >= 0 {
-                    return Some(vals[active as usize]);
-                }
+        for e in game.input.poll() {
+            match list.handle(&e) {
+                HandlerRet::Exit => return None,
+                HandlerRet::Accept => return Some(list.active as usize),
+                HandlerRet::Nothing => (),
             }
-            _ => (),
-        };
+        }
 
         // Draw
         game.canvas.set_draw_color(Color::RGBA(150, 150, 150, 100));
         game.canvas.clear();
 
-        game.canvas.set_draw_color(Color::RGB(0, 0, 0));
-        game.canvas.fill_rect(backdrop);
-
-        // Draw active
-        if active >= 0 {
-            game.canvas.set_draw_color(Color::RGB(50, 50, 50));
-            game.canvas
-                .fill_rect(Rect::new(200, 160 + (active * 40), 200, 40));
-        }
-
-        // Draw texts
-        for (i, texture) in texts.iter().enumerate() {
-            game.canvas.copy(texture, None, Some(rects[i])).unwrap();
-        }
+        backdrop.draw(&mut game.canvas);
+        list.draw(&mut game.canvas, &mut font);
 
         // Update
-        game.canvas.present();
+        game.toasts.present(&mut game.canvas);
+
+        // Sleep
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
-    None
 }
 
 // Display the inventory screen
 pub fn display_inventory(game: &mut Game, mode: Option<InventoryMode>) -> Option<usize> {
-    // State:
-    let mut screen = true; // T: inventory, F: buy
-    let mut active: i32 = -1; // Highlight item on list
-
-    // Load font
-    let mut font = game
+    // Load fonts
+    let mut big_font = game
         .ttf
         .load_font("assets/SupermercadoOne-Regular.ttf", 32)
         .unwrap();
-
-    // Create tc
-    let tc = game.canvas.texture_creator();
-
-    // Label screen
-    let label = create_text("Inventory:", &tc, &mut font, Color::RGB(255, 255, 255));
-    let label2 = create_text("Buy:", &tc, &mut font, Color::RGB(255, 255, 255));
-
-    // Store never changes so do it out here
-    let mut store: Vec<Texture> = Vec::new();
-
-    // Change font
-    font = game
+    let mut font = game
         .ttf
         .load_font("assets/SupermercadoOne-Regular.ttf", 24)
         .unwrap();
+    let mut small_font = game
+        .ttf
+        .load_font("assets/SupermercadoOne-Regular.ttf", 16)
+        .unwrap();
 
-    let mut store_products: Vec<Product> = Vec::new();
-
-    // Create store as well
-    store_products.push(Product::new(Material::Iron));
-    store_products.push(Product::new(Material::Steel));
-    store_products.push(Product::new(Material::Bronze));
-    store_products.push(Product::new(Material::Silver));
-    store_products.push(Product::new(Material::Gold));
+    // Label screen
+    let label = Label::new("Inventory:", Rect::new(20, 20, 560, 50));
 
-    let s1 = store_products[0].to_string();
-    let s2 = store_products[1].to_string();
-    let s3 = store_products[2].to_string();
-    let s4 = store_products[3].to_string();
-    let s5 = store_products[4].to_string();
+    // Five rows show at a time, the rest scroll
+    let mut list = List::new(Rect::new(20, 80, 560, 250), 50);
 
-    store.push(create_text(&s1, &tc, &mut font, Color::RGB(255, 255, 255)));
-    store.push(create_text(&s2, &tc, &mut font, Color::RGB(255, 255, 255)));
-    store.push(create_text(&s3, &tc, &mut font, Color::RGB(255, 255, 255)));
-    store.push(create_text(&s4, &tc, &mut font, Color::RGB(255, 255, 255)));
-    store.push(create_text(&s5, &tc, &mut font, Color::RGB(255, 255, 255)));
+    let mut money = Label::new("", Rect::new(20, 340, 200, 40));
+    let mut storage = Label::new("", Rect::new(200, 340, 170, 40));
+    storage.style = TextStyle::single_line(Align::Right);
+    let mut storage_bar = ProgressBar::new(Rect::new(380, 352, 200, 16), Color::RGB(119, 235, 52));
+    let mut details = Label::new("", Rect::new(20, 385, 560, 90));
+    details.style = TextStyle::new();
 
     // Draw stuff here
-    'scan: loop {
-        // Items in inventory
-        let mut items: Vec<Texture> = Vec::new();
-
-        // Create the textures
-        for (i, item) in game.state.inventory.iter().enumerate() {
-            let s = item.to_string();
-            let c = match item.temp_val() {
-                Temp::Under => Color::RGB(255, 255, 255),
-                Temp::Over => Color::RGB(235, 204, 52),
-                Temp::Perfect => Color::RGB(119, 235, 52),
-            };
-            items.push(create_text(&s, &tc, &mut font, c));
-        }
-
-        // Black background
-        game.canvas.set_draw_color(Color::RGB(0, 0, 0));
-        game.canvas.clear();
-
-        // Tabs
-        game.canvas.set_draw_color(Color::RGB(100, 100, 100));
-        match screen {
-            true => game.canvas.fill_rect(Rect::new(300, 0, 300, 70)).unwrap(),
-            false => game.canvas.fill_rect(Rect::new(0, 0, 300, 70)).unwrap(),
-        };
-
-        // Active highlight
-        match screen {
-            true => {
-                if active >= 0 && active < game.state.inventory.len() as i32 {
-                    game.canvas.set_draw_color(Color::RGB(50, 50, 50));
-                    game.canvas
-                        .fill_rect(Rect::new(
-                            20,
-                            80 + 50 * active,
-                            store[0].query().width + 40,
-                            store[0].query().height,
-                        ))
-                        .unwrap();
-                }
-            }
-            false => {
-                if active >= 0 && active < store.len() as i32 {
-                    game.canvas.set_draw_color(Color::RGB(50, 50, 50));
-                    game.canvas
-                        .fill_rect(Rect::new(
-                            20,
-                            80 + 50 * active,
-                            store[0].query().width + 40,
-                            store[0].query().height,
-                        ))
-                        .unwrap();
-                }
-            }
-        };
-
-        // Draw the labels
-        let label_rect = Rect::new(20, 20, label.query().width, label.query().height);
-        game.canvas.copy(&label, None, Some(label_rect)).unwrap();
-
-        let label_rect2 = Rect::new(320, 20, label2.query().width, label2.query().height);
-        game.canvas.copy(&label2, None, Some(label_rect2)).unwrap();
-
-        let label3 = create_text(
-            &format!("Money: {}$", game.state.money).to_owned(),
-            &tc,
-            &mut font,
-            Color::RGB(255, 255, 255),
+    loop {
+        // Items in inventory, coloured by how hot they are
+        list.set_items(
+            game.state
+                .inventory
+                .iter()
+                .map(|item| (item.to_string(), temp_color(&item.temp_val())))
+                .collect(),
         );
-        let label_rect3 = Rect::new(20, 350, label3.query().width, label3.query().height);
-        game.canvas.copy(&label3, None, Some(label_rect3)).unwrap();
-
-        // Draw each inventory item
-        match screen {
-            true => {
-                for (i, item) in items.iter().enumerate() {
-                    let r = Rect::new(
-                        40,
-                        (80 + 50 * i).try_into().unwrap(),
-                        item.query().width,
-                        item.query().height,
-                    );
-                    game.canvas.copy(item, None, Some(r)).unwrap();
-                }
-            }
-            false => {
-                for (i, item) in store.iter().enumerate() {
-                    let r = Rect::new(
-                        40,
-                        (80 + 50 * i).try_into().unwrap(),
-                        item.query().width,
-                        item.query().height,
-                    );
-                    game.canvas.copy(item, None, Some(r)).unwrap();
-                }
-            }
-        };
 
         // Event handling
-        for e in game.event_pump.poll_iter() {
-            match e {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => break 'scan,
-                Event::KeyDown {
-                    keycode: Some(Keycode::Up),
-                    ..
-                } => {
-                    if active > -1 {
-                        active -= 1;
-                    }
-                }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Down),
-                    ..
-                } => {
-                    if screen && active + 1 < game.state.inventory.len() as i32 {
-                        active += 1;
-                    } else if !screen && active < 5 {
-                        active += 1;
+        for e in game.input.poll() {
+            match list.handle(&e) {
+                HandlerRet::Exit => return None,
+                HandlerRet::Accept => {
+                    if let Some(InventoryMode::Select) = mode {
+                        return Some(list.active as usize);
                     }
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Tab),
-                    ..
-                } => {
-                    if let Some(InventoryMode::BuySell) | None = mode {
-                        screen = !screen;
-                        active = -1;
-                    }
-                }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Return),
-                    ..
-                } => match mode {
-                    None | Some(InventoryMode::BuySell) => {
-                        if !screen
-                            && active >= 0
-                            && active < 5
-                            && game.state.money >= store_products[active as usize].value
-                            && (game.state.inventory.len() as i32)
-                                < game.state.upgrades.storage_space
-                        {
-                            game.state.inventory.push(store_products[active as usize]);
-                            game.state.money -=
-                                store_products[active as usize].material.base_value();
-                        } else if screen
-                            && active >= 0
-                            && active < game.state.inventory.len() as i32
-                        {
-                            game.state.money += game.state.inventory[active as usize].value;
-                            game.state.inventory.remove(active as usize);
-                        }
-                    }
-                    Some(InventoryMode::Select) => {
-                        if screen && active >= 0 && active < game.state.inventory.len() as i32 {
-                            return Some(active as usize);
-                        }
-                    }
-                },
-                _ => (),
+                HandlerRet::Nothing => (),
             }
         }
 
+        money.set_text(&format!("Money: {}$", game.state.money));
+        storage.set_text(&format!(
+            "Storage: {}/{}",
+            game.state.stored_weight(),
+            game.state.upgrades.storage_space
+        ));
+        storage_bar.value = game.state.stored_weight();
+        storage_bar.max = game.state.upgrades.storage_space;
+
+        // Highlighted item, how hot it is and the work done on it
+        details.spans = if list.active >= 0 {
+            product_spans(&game.state.inventory[list.active as usize])
+        } else {
+            Vec::new()
+        };
+
+        // Black background
+        game.canvas.set_draw_color(BACKGROUND);
+        game.canvas.clear();
+
+        label.draw(&mut game.canvas, &mut big_font);
+        list.draw(&mut game.canvas, &mut font);
+        money.draw(&mut game.canvas, &mut font);
+        storage.draw(&mut game.canvas, &mut font);
+        storage_bar.draw(&mut game.canvas);
+        details.draw(&mut game.canvas, &mut small_font);
+
         // Update
-        game.canvas.present();
+        game.toasts.present(&mut game.canvas);
 
         // Sleep
         ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
-
-    // Return None
-    None
 }
@@ -0,0 +1,463 @@
+use super::game::*;
+use super::input::*;
+use super::text::*;
+use super::ui::*;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::ttf::Font;
+use sdl2::video::Window;
+
+// Colours shared by the widgets
+pub const TEXT: Color = Color::RGB(255, 255, 255);
+pub const BACKGROUND: Color = Color::RGB(0, 0, 0);
+pub const HIGHLIGHT: Color = Color::RGB(50, 50, 50);
+pub const BORDER: Color = Color::RGB(120, 120, 120);
+
+// Text in a rect, wrapping by default
+pub struct Label {
+    pub rect: Rect,
+    pub spans: Vec<Span>,
+    // Colour set_text uses
+    pub color: Color,
+    pub style: TextStyle,
+}
+
+impl Label {
+    pub fn new(text: &str, rect: Rect) -> Label {
+        Label {
+            rect,
+            spans: vec![Span::new(text, TEXT)],
+            color: TEXT,
+            style: TextStyle {
+                middle: true,
+                ..TextStyle::new()
+            },
+        }
+    }
+
+    pub fn centered(text: &str, rect: Rect) -> Label {
+        Label {
+            style: TextStyle::centered(),
+            ..Label::new(text, rect)
+        }
+    }
+
+    // Replace the text with plain text in the label's colour
+    pub fn set_text(&mut self, text: &str) {
+        self.spans = vec![Span::new(text, self.color)];
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, font: &mut Font) {
+        draw_spans(canvas, font, &self.spans, self.rect, &self.style);
+    }
+}
+
+// A filled box to put other widgets on
+pub struct Panel {
+    pub rect: Rect,
+    pub color: Color,
+    pub border: bool,
+}
+
+impl Panel {
+    pub fn new(rect: Rect) -> Panel {
+        Panel {
+            rect,
+            color: BACKGROUND,
+            border: false,
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>) {
+        canvas.set_draw_color(self.color);
+        canvas.fill_rect(self.rect).unwrap();
+        if self.border {
+            canvas.set_draw_color(BORDER);
+            canvas.draw_rect(self.rect).unwrap();
+        }
+    }
+}
+
+// Text that can be clicked, lit up while the mouse is over it or it's selected
+pub struct Button {
+    pub rect: Rect,
+    pub text: String,
+    pub hovered: bool,
+}
+
+impl Button {
+    pub fn new(text: &str, rect: Rect) -> Button {
+        Button {
+            rect,
+            text: String::from(text),
+            hovered: false,
+        }
+    }
+
+    // True when clicked
+    pub fn handle(&mut self, e: &InputEvent) -> bool {
+        match *e {
+            InputEvent::MouseMove(x, y) => {
+                self.hovered = self.rect.contains_point((x, y));
+                false
+            }
+            InputEvent::Click(x, y) => self.rect.contains_point((x, y)),
+            _ => false,
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, font: &mut Font) {
+        canvas.set_draw_color(if self.hovered { HIGHLIGHT } else { BACKGROUND });
+        canvas.fill_rect(self.rect).unwrap();
+        canvas.set_draw_color(BORDER);
+        canvas.draw_rect(self.rect).unwrap();
+        let style = TextStyle::single_line(Align::Center);
+        draw_string(canvas, font, &self.text, TEXT, self.rect, &style);
+    }
+}
+
+// A bar filled in proportion to value / max
+pub struct ProgressBar {
+    pub rect: Rect,
+    pub value: i32,
+    pub max: i32,
+    pub color: Color,
+}
+
+impl ProgressBar {
+    pub fn new(rect: Rect, color: Color) -> ProgressBar {
+        ProgressBar {
+            rect,
+            value: 0,
+            max: 1,
+            color,
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>) {
+        canvas.set_draw_color(HIGHLIGHT);
+        canvas.fill_rect(self.rect).unwrap();
+
+        let filled = self.value.clamp(0, self.max.max(1)) as u32 * self.rect.width()
+            / self.max.max(1) as u32;
+        if filled > 0 {
+            canvas.set_draw_color(self.color);
+            canvas
+                .fill_rect(Rect::new(
+                    self.rect.x,
+                    self.rect.y,
+                    filled,
+                    self.rect.height(),
+                ))
+                .unwrap();
+        }
+
+        canvas.set_draw_color(BORDER);
+        canvas.draw_rect(self.rect).unwrap();
+    }
+}
+
+// Vertical list of rows, scrolling when there are more than fit in its rect
+pub struct List {
+    pub rect: Rect,
+    pub row_height: i32,
+    pub items: Vec<(String, Color)>,
+    // Highlighted row, -1 for none
+    pub active: i32,
+    // First row shown
+    pub scroll: i32,
+    pub centered: bool,
+}
+
+impl List {
+    pub fn new(rect: Rect, row_height: i32) -> List {
+        List {
+            rect,
+            row_height,
+            items: Vec::new(),
+            active: -1,
+            scroll: 0,
+            centered: false,
+        }
+    }
+
+    // Swap in new rows, keeping the selection where it can
+    pub fn set_items(&mut self, items: Vec<(String, Color)>) {
+        self.items = items;
+        let len = self.items.len() as i32;
+        if self.active >= len {
+            self.active = len - 1;
+        }
+        self.scroll = self.scroll.clamp(0, (len - self.visible_rows()).max(0));
+    }
+
+    pub fn visible_rows(&self) -> i32 {
+        self.rect.height() as i32 / self.row_height
+    }
+
+    // Where row i is drawn, if it's scrolled into view
+    pub fn row_rect(&self, i: i32) -> Option<Rect> {
+        if i < self.scroll || i >= self.scroll + self.visible_rows() || i >= self.items.len() as i32
+        {
+            return None;
+        }
+        Some(Rect::new(
+            self.rect.x,
+            self.rect.y + (i - self.scroll) * self.row_height,
+            self.rect.width(),
+            self.row_height as u32,
+        ))
+    }
+
+    pub fn row_at(&self, x: i32, y: i32) -> Option<usize> {
+        (self.scroll..self.items.len() as i32)
+            .find(|i| match self.row_rect(*i) {
+                Some(r) => r.contains_point((x, y)),
+                None => false,
+            })
+            .map(|i| i as usize)
+    }
+
+    // Highlight a row, scrolling so it can be seen
+    pub fn select(&mut self, i: i32) {
+        self.active = i;
+        if i < 0 {
+            return;
+        }
+        if i < self.scroll {
+            self.scroll = i;
+        }
+        if i >= self.scroll + self.visible_rows() {
+            self.scroll = i - self.visible_rows() + 1;
+        }
+    }
+
+    // Accept when a row is picked, Exit when backed out of
+    pub fn handle(&mut self, e: &InputEvent) -> HandlerRet {
+        let len = self.items.len() as i32;
+        match *e {
+            InputEvent::Quit | InputEvent::Press(Action::Back) => return HandlerRet::Exit,
+            InputEvent::Press(Action::MoveUp) if self.active > 0 => self.select(self.active - 1),
+            InputEvent::Press(Action::MoveDown) if self.active + 1 < len => {
+                self.select(self.active + 1)
+            }
+            InputEvent::Press(Action::Interact) if self.active >= 0 => return HandlerRet::Accept,
+            InputEvent::MouseMove(x, y) => {
+                if let Some(i) = self.row_at(x, y) {
+                    self.active = i as i32;
+                }
+            }
+            InputEvent::Click(x, y) => {
+                if let Some(i) = self.row_at(x, y) {
+                    self.active = i as i32;
+                    return HandlerRet::Accept;
+                }
+            }
+            // The wheel scrolls the view, not the selection
+            InputEvent::Wheel(dy) => {
+                self.scroll = (self.scroll - dy).clamp(0, (len - self.visible_rows()).max(0));
+            }
+            _ => (),
+        }
+        HandlerRet::Nothing
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, font: &mut Font) {
+        if let Some(r) = self.row_rect(self.active) {
+            canvas.set_draw_color(HIGHLIGHT);
+            canvas.fill_rect(r).unwrap();
+        }
+
+        for i in 0..self.items.len() as i32 {
+            if let Some(r) = self.row_rect(i) {
+                let (s, c) = &self.items[i as usize];
+                // Room either side for the scroll bar
+                let at = Rect::new(r.x + 20, r.y, r.width() - 40, r.height());
+                let style = if self.centered {
+                    TextStyle::single_line(Align::Center)
+                } else {
+                    TextStyle::single_line(Align::Left)
+                };
+                draw_string(canvas, font, s, *c, at, &style);
+            }
+        }
+
+        // Scroll bar down the right hand side when not everything fits
+        let len = self.items.len() as i32;
+        let shown = self.visible_rows();
+        if len > shown {
+            let h = self.rect.height() as i32;
+            let track = Rect::new(self.rect.right() - 6, self.rect.y, 6, h as u32);
+            let thumb = Rect::new(
+                track.x,
+                self.rect.y + h * self.scroll / len,
+                6,
+                (h * shown / len) as u32,
+            );
+            canvas.set_draw_color(HIGHLIGHT);
+            canvas.fill_rect(track).unwrap();
+            canvas.set_draw_color(BORDER);
+            canvas.fill_rect(thumb).unwrap();
+        }
+    }
+}
+
+// A row of tabs along the top of a screen
+pub struct Tabs {
+    pub rect: Rect,
+    pub labels: Vec<String>,
+    pub active: usize,
+}
+
+impl Tabs {
+    pub fn new(rect: Rect, labels: &[&str]) -> Tabs {
+        Tabs {
+            rect,
+            labels: labels.iter().map(|l| String::from(*l)).collect(),
+            active: 0,
+        }
+    }
+
+    fn tab_rect(&self, i: usize) -> Rect {
+        let w = self.rect.width() / self.labels.len() as u32;
+        Rect::new(
+            self.rect.x + (w * i as u32) as i32,
+            self.rect.y,
+            w,
+            self.rect.height(),
+        )
+    }
+
+    // True when the tab changed
+    pub fn handle(&mut self, e: &InputEvent) -> bool {
+        match *e {
+            InputEvent::Press(Action::SwitchTab) => {
+                self.active = (self.active + 1) % self.labels.len();
+                true
+            }
+            InputEvent::Click(x, y) => {
+                match (0..self.labels.len()).find(|i| self.tab_rect(*i).contains_point((x, y))) {
+                    Some(i) if i != self.active => {
+                        self.active = i;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, font: &mut Font) {
+        for (i, label) in self.labels.iter().enumerate() {
+            let r = self.tab_rect(i);
+            canvas.set_draw_color(if i == self.active {
+                HIGHLIGHT
+            } else {
+                BACKGROUND
+            });
+            canvas.fill_rect(r).unwrap();
+            let style = TextStyle::single_line(Align::Center);
+            draw_string(canvas, font, label, TEXT, r, &style);
+        }
+    }
+}
+
+// Panel in the middle of the screen with a message and a row of buttons
+pub struct Modal {
+    pub panel: Panel,
+    pub message: Label,
+    pub buttons: Vec<Button>,
+    // Button that Enter presses
+    pub active: usize,
+}
+
+impl Modal {
+    pub fn new(w: u32, h: u32, message: &str, buttons: &[&str]) -> Modal {
+        let mut panel = Panel::new(Rect::from_center(
+            (SCREEN_WIDTH as i32 / 2, SCREEN_HEIGHT as i32 / 2),
+            w,
+            h,
+        ));
+        panel.border = true;
+
+        // Message fills the panel when there's nothing to press
+        let r = panel.rect;
+        let h = if buttons.is_empty() {
+            r.height() - 20
+        } else {
+            r.height() - 70
+        };
+        let message = Label::centered(message, Rect::new(r.x + 10, r.y + 10, r.width() - 20, h));
+
+        // Buttons share the bottom of the panel
+        let n = buttons.len().max(1) as i32;
+        let w = (r.width() as i32 - 20) / n;
+        let buttons = buttons
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                Button::new(
+                    b,
+                    Rect::new(
+                        r.x + 10 + w * i as i32,
+                        r.bottom() - 50,
+                        (w - 10) as u32,
+                        40,
+                    ),
+                )
+            })
+            .collect();
+
+        Modal {
+            panel,
+            message,
+            buttons,
+            active: 0,
+        }
+    }
+
+    // Accept when a button is pressed, with its index in active
+    pub fn handle(&mut self, e: &InputEvent) -> HandlerRet {
+        for (i, b) in self.buttons.iter_mut().enumerate() {
+            if b.handle(e) {
+                self.active = i;
+                return HandlerRet::Accept;
+            }
+            if let InputEvent::MouseMove(..) = *e {
+                if b.hovered {
+                    self.active = i;
+                }
+            }
+        }
+
+        match *e {
+            InputEvent::Quit | InputEvent::Press(Action::Back) => HandlerRet::Exit,
+            InputEvent::Press(Action::MoveLeft) => {
+                if self.active > 0 {
+                    self.active -= 1;
+                }
+                HandlerRet::Nothing
+            }
+            InputEvent::Press(Action::MoveRight) => {
+                if self.active + 1 < self.buttons.len() {
+                    self.active += 1;
+                }
+                HandlerRet::Nothing
+            }
+            InputEvent::Press(Action::Interact) => HandlerRet::Accept,
+            _ => HandlerRet::Nothing,
+        }
+    }
+
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>, font: &mut Font) {
+        self.panel.draw(canvas);
+        self.message.draw(canvas, font);
+        for (i, b) in self.buttons.iter_mut().enumerate() {
+            b.hovered = i == self.active;
+            b.draw(canvas, font);
+        }
+    }
+}
@@ -0,0 +1,474 @@
+use crate::game::{Expansion, Upgrades};
+use crate::Controls;
+
+use sdl2::rect::Rect;
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Size of a single map tile in pixels
+pub const TILE_SIZE: i32 = 20;
+
+// How far the player moves per frame
+const PLAYER_SPEED: i32 = 7;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Tile {
+    Floor,
+    Wall,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StationKind {
+    Anvil,
+    Forge,
+    Desk,
+    QuenchTub,
+    Grindstone,
+    Rack,
+    Chest,
+}
+
+impl StationKind {
+    pub fn from_name(s: &str) -> Option<StationKind> {
+        match s {
+            "anvil" => Some(StationKind::Anvil),
+            "forge" => Some(StationKind::Forge),
+            "desk" => Some(StationKind::Desk),
+            "quench_tub" => Some(StationKind::QuenchTub),
+            "grindstone" => Some(StationKind::Grindstone),
+            "rack" => Some(StationKind::Rack),
+            "chest" => Some(StationKind::Chest),
+            _ => None,
+        }
+    }
+    pub fn texture_path(&self) -> &'static str {
+        match self {
+            StationKind::Anvil => "assets/Anvil.png",
+            StationKind::Forge => "assets/Forge.png",
+            StationKind::Desk => "assets/Desk.png",
+            StationKind::QuenchTub => "assets/QuenchTub.png",
+            StationKind::Grindstone => "assets/Grindstone.png",
+            StationKind::Rack => "assets/Rack.png",
+            StationKind::Chest => "assets/Chest.png",
+        }
+    }
+    // What the station is for, shown in its tooltip
+    pub fn description(&self) -> String {
+        match self {
+            StationKind::Anvil => String::from("Hammer hot bars into shape"),
+            StationKind::Forge => {
+                String::from("Heat items, smelt crucibles of ore and melt down scrap")
+            }
+            StationKind::Desk => {
+                String::from("Shop, ledger, bank, assembly, upgrades and settings")
+            }
+            StationKind::QuenchTub => String::from("Harden hot blades or just cool them off"),
+            StationKind::Grindstone => String::from("Sharpen and polish shaped items"),
+            StationKind::Rack => String::from("Put items out for passing customers to buy"),
+            StationKind::Chest => String::from("Look through everything in storage"),
+        }
+    }
+}
+
+impl fmt::Display for StationKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            StationKind::Anvil => "Anvil",
+            StationKind::Forge => "Forge",
+            StationKind::Desk => "Desk",
+            StationKind::QuenchTub => "Quench Tub",
+            StationKind::Grindstone => "Grindstone",
+            StationKind::Rack => "Display Rack",
+            StationKind::Chest => "Chest",
+        })
+    }
+}
+
+pub struct Station {
+    pub kind: StationKind,
+    // Where the sprite is drawn
+    pub sprite: Rect,
+    // Part of the station the player bumps into
+    pub solid: Rect,
+    // Where the player has to stand to use it
+    pub zone: Rect,
+    // Upgrade needed before it shows up
+    pub requires: Option<Expansion>,
+    pub built: bool,
+}
+
+pub struct Workshop {
+    pub tiles: Vec<Vec<Tile>>,
+    pub stations: Vec<Station>,
+}
+
+// Read "x y w h" into a rect
+fn parse_rect(args: &[&str], line_no: usize) -> Result<Rect, String> {
+    if args.len() != 4 {
+        return Err(format!("line {}: expected x y w h", line_no));
+    }
+
+    let mut nums = [0i32; 4];
+    for (i, a) in args.iter().enumerate() {
+        nums[i] = a
+            .parse()
+            .map_err(|_| format!("line {}: bad number '{}'", line_no, a))?;
+    }
+    if nums[2] <= 0 || nums[3] <= 0 {
+        return Err(format!(
+            "line {}: width and height must be positive",
+            line_no
+        ));
+    }
+
+    Ok(Rect::new(nums[0], nums[1], nums[2] as u32, nums[3] as u32))
+}
+
+impl Workshop {
+    pub fn load(path: &Path) -> Result<Workshop, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        Workshop::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Workshop, String> {
+        let mut tiles: Vec<Vec<Tile>> = Vec::new();
+        let mut stations: Vec<Station> = Vec::new();
+
+        // Station currently being read
+        let mut current: Option<PartialStation> = None;
+        let mut in_grid = false;
+
+        for (i, raw) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = raw.trim();
+
+            // Grid rows are taken as is
+            if in_grid {
+                if line == "end" {
+                    in_grid = false;
+                    continue;
+                }
+
+                let mut row = Vec::new();
+                for c in line.chars() {
+                    match c {
+                        '#' => row.push(Tile::Wall),
+                        '.' => row.push(Tile::Floor),
+                        _ => return Err(format!("line {}: unknown tile '{}'", line_no, c)),
+                    }
+                }
+                tiles.push(row);
+                continue;
+            }
+
+            // Skip blanks + comments
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[0] {
+                "grid" => in_grid = true,
+                "station" => {
+                    if let Some(s) = current.take() {
+                        stations.push(s.finish(line_no)?);
+                    }
+                    let kind = words
+                        .get(1)
+                        .and_then(|k| StationKind::from_name(k))
+                        .ok_or(format!("line {}: unknown station", line_no))?;
+                    current = Some(PartialStation {
+                        kind,
+                        sprite: None,
+                        solid: None,
+                        zone: None,
+                        requires: None,
+                    });
+                }
+                "requires" => {
+                    let e = words
+                        .get(1)
+                        .and_then(|e| Expansion::from_name(e))
+                        .ok_or(format!("line {}: unknown upgrade", line_no))?;
+                    let s = current
+                        .as_mut()
+                        .ok_or(format!("line {}: 'requires' outside a station", line_no))?;
+                    s.requires = Some(e);
+                }
+                "sprite" | "solid" | "zone" => {
+                    let r = parse_rect(&words[1..], line_no)?;
+                    let s = current.as_mut().ok_or(format!(
+                        "line {}: '{}' outside a station",
+                        line_no, words[0]
+                    ))?;
+                    match words[0] {
+                        "sprite" => s.sprite = Some(r),
+                        "solid" => s.solid = Some(r),
+                        _ => s.zone = Some(r),
+                    }
+                }
+                w => return Err(format!("line {}: unknown keyword '{}'", line_no, w)),
+            }
+        }
+
+        if let Some(s) = current.take() {
+            stations.push(s.finish(text.lines().count())?);
+        }
+
+        if tiles.is_empty() {
+            return Err(String::from("workshop has no grid"));
+        }
+
+        Ok(Workshop { tiles, stations })
+    }
+
+    // Anything outside the grid counts as wall
+    pub fn tile(&self, col: i32, row: i32) -> Tile {
+        if col < 0 || row < 0 {
+            return Tile::Wall;
+        }
+
+        match self
+            .tiles
+            .get(row as usize)
+            .and_then(|r| r.get(col as usize))
+        {
+            Some(t) => *t,
+            None => Tile::Wall,
+        }
+    }
+
+    // Show stations once their upgrade has been bought
+    pub fn update(&mut self, upgrades: &Upgrades) {
+        for s in self.stations.iter_mut() {
+            s.built = match s.requires {
+                Some(e) => upgrades.has(e),
+                None => true,
+            };
+        }
+    }
+
+    // Stations currently in the workshop
+    pub fn built(&self) -> impl Iterator<Item = &Station> {
+        self.stations.iter().filter(|s| s.built)
+    }
+
+    // Whether a step from one rect to the next runs into a wall or a
+    // station. A station built on top of the player only stops steps that
+    // go further into it, so they can always walk back out
+    pub fn blocked(&self, from: Rect, r: Rect) -> bool {
+        for s in self.built() {
+            if overlap(r, s.solid) > overlap(from, s.solid) {
+                return true;
+            }
+        }
+
+        // Check every tile the rect touches
+        let (left, right) = (
+            r.left().div_euclid(TILE_SIZE),
+            (r.right() - 1).div_euclid(TILE_SIZE),
+        );
+        let (top, bottom) = (
+            r.top().div_euclid(TILE_SIZE),
+            (r.bottom() - 1).div_euclid(TILE_SIZE),
+        );
+        for row in top..=bottom {
+            for col in left..=right {
+                if self.tile(col, row) == Tile::Wall {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    // Move the player, sliding along anything solid
+    pub fn move_player(&self, c: &Controls, r: &mut Rect) {
+        let mut dx = 0;
+        let mut dy = 0;
+        if c.up {
+            dy -= PLAYER_SPEED;
+        }
+        if c.down {
+            dy += PLAYER_SPEED;
+        }
+        if c.left {
+            dx -= PLAYER_SPEED;
+        }
+        if c.right {
+            dx += PLAYER_SPEED;
+        }
+
+        // Resolve each axis on its own so walls can be slid along
+        for _ in 0..dx.abs() {
+            let mut next = player_feet(*r);
+            next.offset(dx.signum(), 0);
+            if self.blocked(player_feet(*r), next) {
+                break;
+            }
+            r.x += dx.signum();
+        }
+        for _ in 0..dy.abs() {
+            let mut next = player_feet(*r);
+            next.offset(0, dy.signum());
+            if self.blocked(player_feet(*r), next) {
+                break;
+            }
+            r.y += dy.signum();
+        }
+    }
+
+    // Step the player towards a point, false once they're there or stuck
+    pub fn walk_towards(&self, target: (i32, i32), r: &mut Rect) -> bool {
+        let feet = player_feet(*r).center();
+        let dx = target.0 - feet.x();
+        let dy = target.1 - feet.y();
+
+        let mut c = Controls::new();
+        c.left = dx <= -PLAYER_SPEED;
+        c.right = dx >= PLAYER_SPEED;
+        c.up = dy <= -PLAYER_SPEED;
+        c.down = dy >= PLAYER_SPEED;
+
+        let before = *r;
+        self.move_player(&c, r);
+        *r != before
+    }
+
+    // Station drawn under a point, by index
+    pub fn station_under(&self, x: i32, y: i32) -> Option<usize> {
+        self.stations
+            .iter()
+            .position(|s| s.built && s.sprite.contains_point((x, y)))
+    }
+
+    // Station the player is standing at, if any
+    pub fn station_at(&self, player: Rect) -> Option<&Station> {
+        let feet = player_feet(player);
+        self.built().find(|s| s.zone.has_intersection(feet))
+    }
+}
+
+// A station that hasn't had all of its lines read yet
+struct PartialStation {
+    kind: StationKind,
+    sprite: Option<Rect>,
+    solid: Option<Rect>,
+    zone: Option<Rect>,
+    requires: Option<Expansion>,
+}
+
+impl PartialStation {
+    fn finish(self, line_no: usize) -> Result<Station, String> {
+        match (self.sprite, self.solid, self.zone) {
+            (Some(sprite), Some(solid), Some(zone)) => Ok(Station {
+                kind: self.kind,
+                sprite,
+                solid,
+                zone,
+                requires: self.requires,
+                built: self.requires.is_none(),
+            }),
+            _ => Err(format!(
+                "line {}: station needs sprite, solid and zone",
+                line_no
+            )),
+        }
+    }
+}
+
+// Area two rects share
+fn overlap(a: Rect, b: Rect) -> u32 {
+    match a.intersection(b) {
+        Some(i) => i.width() * i.height(),
+        None => 0,
+    }
+}
+
+// The part of the player sprite that collides (their feet)
+pub fn player_feet(r: Rect) -> Rect {
+    Rect::new(r.x + 30, r.y + 90, 60, 30)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYOUT: &str = "\
+# Two by three room
+grid
+###
+#..
+end
+
+station desk
+sprite 0 0 20 20
+solid 0 10 20 10
+zone 0 20 20 20
+
+station grindstone
+requires grindstone
+sprite 40 0 20 20
+solid 40 10 20 10
+zone 40 20 20 20
+";
+
+    #[test]
+    fn parses_grid_and_stations() {
+        let w = Workshop::parse(LAYOUT).unwrap();
+        assert_eq!(w.tiles.len(), 2);
+        assert!(w.tile(0, 1) == Tile::Wall);
+        assert!(w.tile(1, 1) == Tile::Floor);
+        assert!(w.tile(5, 5) == Tile::Wall);
+
+        assert_eq!(w.stations.len(), 2);
+        assert!(w.stations[0].kind == StationKind::Desk);
+        assert!(w.stations[0].built);
+        assert_eq!(w.stations[0].solid, Rect::new(0, 10, 20, 10));
+        assert!(w.stations[1].kind == StationKind::Grindstone);
+        assert!(!w.stations[1].built);
+    }
+
+    #[test]
+    fn shipped_layout_parses() {
+        let w = Workshop::parse(include_str!("../assets/workshop.txt")).unwrap();
+        assert!(w.stations.iter().any(|s| s.kind == StationKind::Desk));
+    }
+
+    #[test]
+    fn rejects_bad_layouts() {
+        let bad = [
+            "grid\n#x#\nend\n",
+            "station desk\nsprite 0 0 20 20\n",
+            "station well\n",
+            "sprite 0 0 20 20\n",
+            "grid\n###\nend\nstation desk\nsprite 0 0 twenty 20\n",
+            "grid\n###\nend\nstation desk\nzone 0 0 20\n",
+            "grid\n###\nend\nstation desk\nsolid 0 0 -20 20\n",
+            "grid\n###\nend\nstation desk\nsprite 0 0 20 0\n",
+            "grid\n###\nend\nbench\n",
+            "# Just a comment\n",
+        ];
+        for text in bad {
+            assert!(Workshop::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn errors_give_the_line() {
+        let text = "grid\n###\nend\nstation desk\nsprite 0 0 x 20\n";
+        match Workshop::parse(text) {
+            Err(e) => assert!(e.starts_with("line 5:"), "{}", e),
+            Ok(_) => panic!("parsed a bad rect"),
+        }
+
+        let text = "grid\n###\nend\nstation desk\nsprite 0 0 20 20\nzone 0 0 20 -5\n";
+        match Workshop::parse(text) {
+            Err(e) => assert!(e.starts_with("line 6:"), "{}", e),
+            Ok(_) => panic!("parsed a negative size"),
+        }
+    }
+}